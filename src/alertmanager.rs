@@ -0,0 +1,512 @@
+//! Render [Prometheus Alertmanager](https://prometheus.io/docs/alerting/latest/configuration/#webhook_config)
+//! webhook notifications as slack messages
+//!
+//! ```
+//! use slack_hook::alertmanager::Notification;
+//!
+//! let json = r#"{
+//!     "status": "firing",
+//!     "groupLabels": {"alertname": "DiskFull"},
+//!     "commonLabels": {"alertname": "DiskFull", "severity": "critical"},
+//!     "alerts": [{
+//!         "status": "firing",
+//!         "labels": {"alertname": "DiskFull", "severity": "critical", "instance": "db-1"},
+//!         "annotations": {"summary": "/var is 98% full"},
+//!         "startsAt": "2024-05-01T12:00:00Z",
+//!         "generatorURL": "https://prometheus.example.com/graph"
+//!     }]
+//! }"#;
+//! let notification: Notification = serde_json::from_str(json).unwrap();
+//! let payload = notification.to_payload().unwrap();
+//! ```
+//!
+//! Implement [`Template`] to change how notifications are rendered.
+
+use crate::civil::{civil_from_days, days_from_civil};
+use crate::{
+    AttachmentBuilder, Field, HexColor, Payload, PayloadBuilder, Result, Section, SlackColor,
+    SlackLink, SlackText, SlackTextContent, SlackTime,
+};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+
+/// Whether alerts are still firing
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// At least one alert is firing
+    Firing,
+    /// Everything has resolved
+    Resolved,
+}
+
+/// A webhook notification, holding one group of alerts
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    /// Status of the group as a whole
+    pub status: Status,
+    /// Name of the receiver the notification was sent to
+    #[serde(default)]
+    pub receiver: String,
+    /// Key identifying the group
+    #[serde(default)]
+    pub group_key: String,
+    /// Labels the alerts were grouped by
+    #[serde(default)]
+    pub group_labels: BTreeMap<String, String>,
+    /// Labels shared by every alert
+    #[serde(default)]
+    pub common_labels: BTreeMap<String, String>,
+    /// Annotations shared by every alert
+    #[serde(default)]
+    pub common_annotations: BTreeMap<String, String>,
+    /// Link back to the Alertmanager
+    #[serde(rename = "externalURL", default)]
+    pub external_url: Option<String>,
+    /// Number of alerts left out because of the receiver's `max_alerts`
+    #[serde(default)]
+    pub truncated_alerts: u64,
+    /// The alerts themselves
+    pub alerts: Vec<Alert>,
+}
+
+/// A single alert
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    /// Whether the alert is firing
+    pub status: Status,
+    /// Labels identifying the alert
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Extra information, like `summary` and `description`
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+    /// When the alert started firing
+    #[serde(deserialize_with = "deserialize_rfc3339")]
+    pub starts_at: SlackTime,
+    /// When the alert resolved, if it has
+    #[serde(default, deserialize_with = "deserialize_optional_rfc3339")]
+    pub ends_at: Option<SlackTime>,
+    /// Link to the expression that triggered the alert
+    #[serde(rename = "generatorURL", default)]
+    pub generator_url: Option<String>,
+    /// Identifies the alert across notifications
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+impl Notification {
+    /// Render the notification with the [`DefaultTemplate`]
+    pub fn to_payload(&self) -> Result<Payload> {
+        self.render(&DefaultTemplate)
+    }
+
+    /// Render the notification as one attachment with a custom template
+    pub fn render<T: Template + ?Sized>(&self, template: &T) -> Result<Payload> {
+        let title = template.title(self);
+        let lines = self
+            .alerts
+            .iter()
+            .map(|alert| template.alert_text(self, alert).to_string())
+            .collect::<Vec<_>>();
+
+        let mut attachment = AttachmentBuilder::new(title.as_str())
+            .title(title.as_str())
+            .color(template.color(self))
+            .text(SlackText::new_raw(lines.join("\n")))
+            .fields(template.fields(self))
+            .markdown_in(&[Section::Text]);
+        if let Some(url) = self.external_url.as_deref().filter(|url| !url.is_empty()) {
+            attachment = attachment.title_link(url);
+        }
+        if let Some(starts_at) = self.alerts.iter().map(|alert| alert.starts_at).min() {
+            attachment = attachment.ts(starts_at);
+        }
+        if self.truncated_alerts > 0 {
+            attachment = attachment.footer(format!("{} more alerts", self.truncated_alerts));
+        }
+
+        PayloadBuilder::new()
+            .attachments(vec![template.finish(self, attachment).build()?])
+            .build()
+    }
+
+    /// Name shared by the alerts, if they have one
+    pub fn alert_name(&self) -> Option<&str> {
+        self.group_labels
+            .get("alertname")
+            .or_else(|| self.common_labels.get("alertname"))
+            .map(String::as_str)
+    }
+
+    /// Alerts that are still firing
+    pub fn firing(&self) -> impl Iterator<Item = &Alert> {
+        self.alerts
+            .iter()
+            .filter(|alert| alert.status == Status::Firing)
+    }
+}
+
+/// Customizes how a [`Notification`] is rendered
+///
+/// Every method has a default, so implementations only override the parts they care about.
+///
+/// ```
+/// use slack_hook::alertmanager::{Notification, Template};
+/// use slack_hook::AttachmentBuilder;
+///
+/// struct OnCall;
+///
+/// impl Template for OnCall {
+///     fn finish(&self, _: &Notification, attachment: AttachmentBuilder) -> AttachmentBuilder {
+///         attachment.pretext("<!subteam^S0123|oncall>")
+///     }
+/// }
+/// ```
+pub trait Template {
+    /// Title of the attachment, e.g. `[FIRING:2] DiskFull`
+    fn title(&self, notification: &Notification) -> String {
+        let name = notification.alert_name().unwrap_or("alerts");
+        match notification.status {
+            Status::Firing => format!("[FIRING:{}] {name}", notification.firing().count()),
+            Status::Resolved => format!("[RESOLVED] {name}"),
+        }
+    }
+
+    /// Color of the attachment, from the most severe firing alert
+    fn color(&self, notification: &Notification) -> HexColor {
+        if notification.status == Status::Resolved {
+            return SlackColor::Good.into();
+        }
+        notification
+            .firing()
+            .filter_map(|alert| alert.labels.get("severity"))
+            .map(|severity| Severity::from_label(severity))
+            .max()
+            .unwrap_or(Severity::Critical)
+            .color()
+    }
+
+    /// Fields for the labels shared by every alert
+    fn fields(&self, notification: &Notification) -> Vec<Field> {
+        notification
+            .common_labels
+            .iter()
+            .filter(|(name, _)| name.as_str() != "alertname")
+            .map(|(name, value)| Field::new(name.as_str(), value.as_str(), Some(true)))
+            .collect()
+    }
+
+    /// A line describing one alert, linking to its generator url
+    fn alert_text(&self, notification: &Notification, alert: &Alert) -> SlackText {
+        let summary = ["summary", "description", "message"]
+            .iter()
+            .find_map(|key| alert.annotations.get(*key))
+            .or_else(|| alert.labels.get("alertname"))
+            .map_or("alert", String::as_str);
+        // the labels that tell this alert apart from the rest of the group
+        let labels = alert
+            .labels
+            .iter()
+            .filter(|(name, _)| !notification.common_labels.contains_key(*name))
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>();
+
+        let mut text = format!("• {summary}");
+        if !labels.is_empty() {
+            text = format!("{text} ({})", labels.join(", "));
+        }
+        let mut content = vec![SlackTextContent::Text(text.into())];
+        if let Some(url) = alert.generator_url.as_deref().filter(|url| !url.is_empty()) {
+            content.push(SlackTextContent::Link(SlackLink::new(url, "source")));
+        }
+        SlackText::from(&content[..])
+    }
+
+    /// Make any final changes to the attachment before it is built
+    fn finish(
+        &self,
+        notification: &Notification,
+        attachment: AttachmentBuilder,
+    ) -> AttachmentBuilder {
+        let _ = notification;
+        attachment
+    }
+}
+
+/// Renders notifications with the [`Template`] defaults
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultTemplate;
+
+impl Template for DefaultTemplate {}
+
+/// Severity levels, ordered from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    fn from_label(label: &str) -> Severity {
+        match label.to_ascii_lowercase().as_str() {
+            "info" | "informational" | "low" | "none" => Severity::Info,
+            "warning" | "warn" | "medium" => Severity::Warning,
+            // anything unrecognized is treated as critical so it isn't overlooked
+            _ => Severity::Critical,
+        }
+    }
+
+    fn color(self) -> HexColor {
+        match self {
+            Severity::Info => HexColor::from_rgb(0x43, 0x9f, 0xe0),
+            Severity::Warning => SlackColor::Warning.into(),
+            Severity::Critical => SlackColor::Danger.into(),
+        }
+    }
+}
+
+fn deserialize_rfc3339<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<SlackTime, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_rfc3339(&s).ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp `{s}`")))
+}
+
+/// Alertmanager sends Go's zero time, `0001-01-01T00:00:00Z`, for alerts that haven't ended
+fn deserialize_optional_rfc3339<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<SlackTime>, D::Error> {
+    Ok(Some(deserialize_rfc3339(deserializer)?).filter(|time| time.timestamp() > 0))
+}
+
+/// Parse an RFC 3339 timestamp like `2024-05-01T12:00:00.5+02:00`
+fn parse_rfc3339(s: &str) -> Option<SlackTime> {
+    /// A number of exactly `len` digits, which keeps every field small enough not to overflow
+    fn number(s: &str, len: usize) -> Option<i64> {
+        if s.len() == len && s.bytes().all(|b| b.is_ascii_digit()) {
+            s.parse().ok()
+        } else {
+            None
+        }
+    }
+
+    let (date, time) = s.split_once(['T', 't', ' '])?;
+    let (year, month, day) = match date.split('-').collect::<Vec<_>>()[..] {
+        [year, month, day] => (number(year, 4)?, number(month, 2)?, number(day, 2)?),
+        _ => return None,
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    // a day past the end of its month, e.g. `02-30`, lands in the next one
+    if civil_from_days(days) != (year, month, day) {
+        return None;
+    }
+
+    let (time, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(i) => time.split_at(i),
+        None => return None,
+    };
+    let offset = match offset {
+        "Z" | "z" => 0,
+        _ => {
+            let (sign, offset) = offset.split_at(1);
+            let (hours, minutes) = offset.split_once(':')?;
+            let (hours, minutes) = (number(hours, 2)?, number(minutes, 2)?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = hours * 3600 + minutes * 60;
+            if sign == "-" {
+                -offset
+            } else {
+                offset
+            }
+        }
+    };
+
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction))
+            if !fraction.is_empty() && fraction.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            (time, fraction)
+        }
+        Some(_) => return None,
+        None => (time, ""),
+    };
+    let (hour, minute, second) = match time.split(':').collect::<Vec<_>>()[..] {
+        [hour, minute, second] => (number(hour, 2)?, number(minute, 2)?, number(second, 2)?),
+        _ => return None,
+    };
+    // 60 is a leap second
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    // pad or cut the fraction to nine digits of nanoseconds
+    let nanos: u32 = format!("{fraction:0<9}")[..9].parse().ok()?;
+
+    let secs = days
+        .checked_mul(86_400)?
+        .checked_add(hour * 3600 + minute * 60 + second)?
+        .checked_sub(offset)?;
+    Some(SlackTime::from_unix(secs, nanos))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_json_snapshot;
+
+    const NOTIFICATION: &str = r#"{
+        "version": "4",
+        "groupKey": "{}:{alertname=\"HighLatency\"}",
+        "truncatedAlerts": 3,
+        "status": "firing",
+        "receiver": "slack",
+        "groupLabels": {"alertname": "HighLatency"},
+        "commonLabels": {"alertname": "HighLatency", "job": "api"},
+        "commonAnnotations": {},
+        "externalURL": "https://alertmanager.example.com",
+        "alerts": [
+            {
+                "status": "firing",
+                "labels": {"alertname": "HighLatency", "job": "api", "instance": "api-1", "severity": "warning"},
+                "annotations": {"summary": "p99 latency is 2.1s"},
+                "startsAt": "2024-05-01T12:00:05.123Z",
+                "endsAt": "0001-01-01T00:00:00Z",
+                "generatorURL": "https://prometheus.example.com/graph?g0.expr=latency",
+                "fingerprint": "c0ffee"
+            },
+            {
+                "status": "firing",
+                "labels": {"alertname": "HighLatency", "job": "api", "instance": "api-2", "severity": "info"},
+                "annotations": {},
+                "startsAt": "2024-05-01T14:00:00+02:00",
+                "generatorURL": ""
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn firing() {
+        let notification: Notification = serde_json::from_str(NOTIFICATION).unwrap();
+        assert_eq!(notification.alerts[0].ends_at, None);
+        assert_json_snapshot!(notification.to_payload().unwrap(), @r###"
+        {
+          "attachments": [
+            {
+              "fallback": "[FIRING:2] HighLatency",
+              "text": "• p99 latency is 2.1s (instance=api-1, severity=warning) <https://prometheus.example.com/graph?g0.expr=latency|source>\n• HighLatency (instance=api-2, severity=info)",
+              "color": "warning",
+              "fields": [
+                {
+                  "title": "job",
+                  "value": "api",
+                  "short": true
+                }
+              ],
+              "title": "[FIRING:2] HighLatency",
+              "title_link": "https://alertmanager.example.com/",
+              "footer": "3 more alerts",
+              "ts": 1714564800,
+              "mrkdwn_in": [
+                "text"
+              ]
+            }
+          ]
+        }
+        "###);
+    }
+
+    #[test]
+    fn resolved() {
+        let json = NOTIFICATION.replace("\"firing\"", "\"resolved\"");
+        let notification: Notification = serde_json::from_str(&json).unwrap();
+        let payload = notification.to_payload().unwrap();
+        let attachment = &payload.attachments.unwrap()[0];
+        assert_eq!(attachment.color, Some(SlackColor::Good.into()));
+        assert_eq!(
+            attachment.title.as_ref().unwrap().to_string(),
+            "[RESOLVED] HighLatency"
+        );
+    }
+
+    #[test]
+    fn custom_template() {
+        struct Terse;
+
+        impl Template for Terse {
+            fn fields(&self, _: &Notification) -> Vec<Field> {
+                Vec::new()
+            }
+
+            fn finish(&self, _: &Notification, attachment: AttachmentBuilder) -> AttachmentBuilder {
+                attachment.footer("prod")
+            }
+        }
+
+        let notification: Notification = serde_json::from_str(NOTIFICATION).unwrap();
+        let payload = notification.render(&Terse).unwrap();
+        let attachment = &payload.attachments.unwrap()[0];
+        assert_eq!(attachment.fields.as_deref(), Some(&[][..]));
+        assert_eq!(attachment.footer.as_ref().unwrap().to_string(), "prod");
+    }
+
+    #[test]
+    fn timestamps() {
+        let time = parse_rfc3339("2024-05-01T12:00:05.123456789Z").unwrap();
+        assert_eq!(
+            (time.timestamp(), time.subsec_nanos()),
+            (1_714_564_805, 123_456_789)
+        );
+        let time = parse_rfc3339("1970-01-01T01:00:00.5+01:00").unwrap();
+        assert_eq!((time.timestamp(), time.subsec_nanos()), (0, 500_000_000));
+        assert_eq!(parse_rfc3339("2024-13-01T00:00:00Z"), None);
+        assert_eq!(parse_rfc3339("yesterday"), None);
+    }
+
+    #[test]
+    fn malformed_timestamps() {
+        for s in [
+            "2023-02-29T00:00:00Z",
+            "2024-04-31T00:00:00Z",
+            "2024-05-01T24:00:00Z",
+            "2024-05-01T12:60:00Z",
+            "2024-05-01T12:00:61Z",
+            "2024-05-01T12:00:00+24:00",
+            "2024-05-01T12:00:00+01:60",
+            "2024-05-01T12:00:00.Z",
+            "2024-05-01T12:00:00.5xZ",
+            "2024-05-01T12:00:00.1234567890xZ",
+            "2024-05-01T1:00:00Z",
+            "2024-05-01T12:00:00+1:00",
+            "2024-5-01T12:00:00Z",
+            "-2024-05-01T12:00:00Z",
+            "2024-05-01T12:00:00",
+        ] {
+            assert_eq!(parse_rfc3339(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn huge_timestamps() {
+        for s in [
+            "9223372036854775807-12-31T23:59:59Z",
+            "99999999999999999999-01-01T00:00:00Z",
+            "2024-05-01T9223372036854775807:00:00Z",
+            "2024-05-01T12:00:00+9223372036854775807:00",
+        ] {
+            assert_eq!(parse_rfc3339(s), None, "{}", s);
+        }
+        let time = parse_rfc3339("9999-12-31T23:59:60.999999999999-23:59").unwrap();
+        assert_eq!(
+            (time.timestamp(), time.subsec_nanos()),
+            (253_402_300_800 + 86_340, 999_999_999)
+        );
+    }
+}
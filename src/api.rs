@@ -0,0 +1,95 @@
+use crate::{Error, Result};
+use reqwest::{header::RETRY_AFTER, Client, RequestBuilder, StatusCode, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+/// Base url that all of the Web API methods hang off of
+pub(crate) const SLACK_API_URL: &str = "https://slack.com/api/";
+
+pub(crate) fn default_api_url() -> Url {
+    Url::parse(SLACK_API_URL).expect("the default api url is valid")
+}
+
+/// Add the trailing slash that method names get joined onto
+pub(crate) fn normalize_api_url(mut api_url: Url) -> Url {
+    if !api_url.path().ends_with('/') {
+        let path = format!("{}/", api_url.path());
+        api_url.set_path(&path);
+    }
+    api_url
+}
+
+/// Calls a Web API `method` with a JSON `body`, authenticating with `token`
+///
+/// Slack reports method failures with a `200` status and `"ok": false`, so both the HTTP status
+/// and the `ok` flag are checked before `T` is deserialized from the response.
+pub(crate) async fn post_json<B, T>(
+    client: &Client,
+    api_url: &Url,
+    token: &str,
+    method: &str,
+    body: &B,
+) -> Result<T>
+where
+    B: Serialize + ?Sized,
+    T: DeserializeOwned,
+{
+    send(
+        client
+            .post(api_url.join(method)?)
+            .bearer_auth(token)
+            .json(body),
+    )
+    .await
+}
+
+/// Calls a Web API `method` with a form encoded `body`, which the read methods require
+#[cfg(feature = "web-api")]
+pub(crate) async fn post_form<B, T>(
+    client: &Client,
+    api_url: &Url,
+    token: &str,
+    method: &str,
+    body: &B,
+) -> Result<T>
+where
+    B: Serialize + ?Sized,
+    T: DeserializeOwned,
+{
+    send(
+        client
+            .post(api_url.join(method)?)
+            .bearer_auth(token)
+            .form(body),
+    )
+    .await
+}
+
+async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
+    let response = request.send().await?;
+    // methods are rate limited by tier, e.g. `reactions.add` to about 50 calls a minute
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response.headers().get(RETRY_AFTER);
+        return Err(Error::rate_limited(
+            retry_after.and_then(|value| value.to_str().ok()),
+        ));
+    }
+    if !response.status().is_success() {
+        return Err(Error::Slack(format!("HTTP error {}", response.status())));
+    }
+
+    let value: Value = response.json().await?;
+    parse_response(value)
+}
+
+fn parse_response<T: DeserializeOwned>(value: Value) -> Result<T> {
+    if value.get("ok").and_then(Value::as_bool) == Some(true) {
+        Ok(serde_json::from_value(value)?)
+    } else {
+        let error = value
+            .get("error")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown_error");
+        Err(Error::Api(error.to_owned()))
+    }
+}
@@ -1,13 +1,20 @@
 use crate::error::{Error, Result};
-use crate::{HexColor, SlackText, SlackTime};
-use chrono::NaiveDateTime;
-use reqwest::Url;
-use serde::Serialize;
-use std::convert::TryInto;
+use crate::{HexColor, SlackColor, SlackText, SlackTime};
+use serde::{Deserialize, Serialize};
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    convert::TryInto,
+    error::Error as StdError,
+    iter,
+};
+use url::Url;
+
+/// Most lines of a backtrace included in an error report
+const MAX_BACKTRACE_LINES: usize = 30;
 
 /// Slack allows for attachments to be added to messages. See
 /// <https://api.slack.com/docs/attachments> for more information.
-#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct Attachment {
     /// Required text for attachment.
     /// Slack will use this text to display on devices that don't support markup.
@@ -70,8 +77,84 @@ pub struct Attachment {
     pub callback_id: Option<SlackText>,
 }
 
+impl Attachment {
+    /// Report an error, colored `danger`
+    ///
+    /// The error's message becomes the title, while each error in its `source()` chain is added as
+    /// a numbered field.
+    pub fn from_error(err: &dyn StdError) -> Attachment {
+        let causes = iter::successors(err.source(), |&cause| cause.source())
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let message = err.to_string();
+        let fallback = iter::once(message.as_str())
+            .chain(causes.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(": ");
+        let fields = causes
+            .iter()
+            .enumerate()
+            .map(|(i, cause)| Field::new(format!("Cause {}", i + 1), cause.as_str(), None))
+            .collect::<Vec<_>>();
+
+        Attachment {
+            fallback: fallback.into(),
+            title: Some(message.into()),
+            color: Some(SlackColor::Danger.into()),
+            fields: Some(fields).filter(|fields| !fields.is_empty()),
+            ..Default::default()
+        }
+    }
+
+    /// Report an error like [`Attachment::from_error()`], adding the backtrace as a code block if
+    /// one was captured
+    ///
+    /// Long backtraces are cut down to their first frames.
+    pub fn from_error_with_backtrace(err: &dyn StdError, backtrace: &Backtrace) -> Attachment {
+        let mut attachment = Attachment::from_error(err);
+        if backtrace.status() == BacktraceStatus::Captured {
+            attachment.text = Some(backtrace_block(&backtrace.to_string()));
+            attachment.mrkdwn_in = Some(vec![Section::Text]);
+        }
+        attachment
+    }
+}
+
+#[cfg(feature = "anyhow")]
+#[cfg_attr(docsrs, doc(cfg(feature = "anyhow")))]
+impl From<&anyhow::Error> for Attachment {
+    fn from(err: &anyhow::Error) -> Attachment {
+        Attachment::from_error_with_backtrace(err.as_ref(), err.backtrace())
+    }
+}
+
+#[cfg(feature = "eyre")]
+#[cfg_attr(docsrs, doc(cfg(feature = "eyre")))]
+impl From<&eyre::Report> for Attachment {
+    fn from(err: &eyre::Report) -> Attachment {
+        Attachment::from_error(err.as_ref())
+    }
+}
+
+fn backtrace_block(backtrace: &str) -> SlackText {
+    let lines = backtrace.lines().collect::<Vec<_>>();
+    let mut block = lines
+        .iter()
+        .take(MAX_BACKTRACE_LINES)
+        .copied()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if lines.len() > MAX_BACKTRACE_LINES {
+        block = format!(
+            "{block}\n… {} more lines",
+            lines.len() - MAX_BACKTRACE_LINES
+        );
+    }
+    SlackText::new(format!("```\n{block}\n```"))
+}
+
 /// Sections define parts of an attachment.
-#[derive(Eq, PartialEq, Copy, Clone, Serialize, Debug)]
+#[derive(Eq, PartialEq, Copy, Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum Section {
     /// The pretext section.
@@ -84,7 +167,7 @@ pub enum Section {
 
 /// Actions are defined as an array, and values contained within it will
 /// be displayed with the message.
-#[derive(Serialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Action {
     /// Action type, renamed to 'type'
     #[serde(rename = "type")]
@@ -122,7 +205,7 @@ impl Action {
 
 /// Fields are defined as an array, and hashes contained within it will
 /// be displayed in a table inside the message attachment.
-#[derive(Serialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Field {
     /// Shown as a bold heading above the value text.
     /// It cannot contain markup and will be escaped for you.
@@ -188,13 +271,18 @@ impl AttachmentBuilder {
     /// 1. `String`s: `good`, `warning`, `danger`
     /// 2. The built-in enums: `SlackColor::Good`, etc.
     /// 3. Any valid hex color code: e.g. `#b13d41` or `#000`.
+    /// 4. A [`HexColor`], e.g. from [`HexColor::from_rgb()`]
     ///
     /// hex color codes will be checked to ensure a valid hex number is provided
-    pub fn color<C: TryInto<HexColor, Error = Error>>(mut self, color: C) -> Self {
+    pub fn color<C>(mut self, color: C) -> Self
+    where
+        C: TryInto<HexColor>,
+        C::Error: Into<Error>,
+    {
         if let Ok(inner) = &mut self.inner {
             match color.try_into() {
                 Ok(c) => inner.color = Some(c),
-                Err(err) => self.inner = Err(err),
+                Err(err) => self.inner = Err(err.into()),
             }
         }
         self
@@ -286,9 +374,12 @@ impl AttachmentBuilder {
     }
 
     /// Optional timestamp to be displayed with the attachment
-    pub fn ts(mut self, time: &NaiveDateTime) -> Self {
+    ///
+    /// Accepts anything convertible to a [`SlackTime`], e.g. a `SystemTime` or a `chrono::DateTime`
+    /// in any time zone.
+    pub fn ts<T: Into<SlackTime>>(mut self, time: T) -> Self {
         if let Ok(inner) = &mut self.inner {
-            inner.ts = Some(SlackTime::new(time));
+            inner.ts = Some(time.into());
         }
         self
     }
@@ -312,3 +403,94 @@ impl AttachmentBuilder {
         self.inner
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::{assert_json_snapshot, assert_snapshot};
+    use std::fmt;
+
+    #[derive(Debug)]
+    struct Failure(&'static str, Option<Box<Failure>>);
+
+    impl fmt::Display for Failure {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.0)
+        }
+    }
+
+    impl StdError for Failure {
+        fn source(&self) -> Option<&(dyn StdError + 'static)> {
+            let source: &Failure = self.1.as_deref()?;
+            Some(source)
+        }
+    }
+
+    #[test]
+    fn error_chain() {
+        let err = Failure(
+            "nightly backup failed",
+            Some(Box::new(Failure(
+                "uploading <archive>",
+                Some(Box::new(Failure("connection reset", None))),
+            ))),
+        );
+        assert_json_snapshot!(Attachment::from_error(&err), @r###"
+        {
+          "fallback": "nightly backup failed: uploading &lt;archive&gt;: connection reset",
+          "color": "danger",
+          "fields": [
+            {
+              "title": "Cause 1",
+              "value": "uploading &lt;archive&gt;"
+            },
+            {
+              "title": "Cause 2",
+              "value": "connection reset"
+            }
+          ],
+          "title": "nightly backup failed"
+        }
+        "###);
+    }
+
+    #[test]
+    fn no_sources() {
+        let attachment = Attachment::from_error(&Failure("oops", None));
+        assert_eq!(attachment.fields, None);
+        assert_eq!(attachment.text, None);
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn anyhow_context() {
+        let err = anyhow::anyhow!("connection reset").context("uploading archive");
+        let attachment = Attachment::from(&err);
+        assert_eq!(
+            attachment.fallback.to_string(),
+            "uploading archive: connection reset"
+        );
+    }
+
+    #[test]
+    fn backtrace_is_truncated() {
+        let backtrace = (0..40).map(|i| format!("{i}: frame")).collect::<Vec<_>>();
+        let block = backtrace_block(&backtrace.join("\n")).to_string();
+        assert_eq!(block.lines().count(), MAX_BACKTRACE_LINES + 3);
+        assert_snapshot!(block.lines().rev().nth(1).unwrap(), @"… 10 more lines");
+    }
+
+    #[test]
+    fn captured_backtrace() {
+        let attachment = Attachment::from_error_with_backtrace(
+            &Failure("oops", None),
+            &Backtrace::force_capture(),
+        );
+        assert!(attachment.text.unwrap().to_string().starts_with("```\n"));
+        assert_eq!(attachment.mrkdwn_in, Some(vec![Section::Text]));
+
+        let attachment =
+            Attachment::from_error_with_backtrace(&Failure("oops", None), &Backtrace::disabled());
+        assert_eq!(attachment.text, None);
+    }
+}
@@ -0,0 +1,12 @@
+//! What the senders that work from spawned Tokio tasks have in common
+
+use crate::Error;
+use std::sync::Arc;
+
+/// Receives the errors of sends made from a spawned task, which have no caller to return them to
+pub(crate) type ErrorHandler = Arc<dyn Fn(Error) + Send + Sync>;
+
+/// The default [`ErrorHandler`], which drops the errors
+pub(crate) fn ignore_errors() -> ErrorHandler {
+    Arc::new(|_| {})
+}
@@ -0,0 +1,19 @@
+use crate::{read_input, Sender};
+use clap::Args;
+use slack_hook::{alertmanager::Notification, Result};
+use std::{path::PathBuf, process::ExitCode};
+
+#[derive(Args, Debug)]
+pub struct AlertmanagerArgs {
+    /// File holding the notification JSON, or `-` to read it from stdin
+    #[arg(default_value = "-")]
+    file: PathBuf,
+}
+
+impl AlertmanagerArgs {
+    pub fn run(&self, sender: &Sender) -> Result<ExitCode> {
+        let notification: Notification = serde_json::from_str(&read_input(&self.file)?)?;
+        sender.send(&notification.to_payload()?)?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,155 @@
+//! `slack-hook` sends messages to slack from the command line
+
+mod alertmanager;
+mod outbox;
+mod run;
+mod send;
+mod tail;
+
+use clap::{Args, Parser, Subcommand};
+use slack_hook::{blocking::Slack, config::Config, Error, Payload, Result, WebhookUrl};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+/// Send messages to slack through an incoming webhook
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    #[command(flatten)]
+    target: Target,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send a text message, an attachment or a raw JSON payload
+    Send(send::SendArgs),
+    /// Run a command and report how it went, e.g. `slack-hook run -- make release`
+    Run(run::RunArgs),
+    /// Stream lines from stdin, batched into code blocks, e.g. `./deploy.sh | slack-hook tail`
+    Tail(tail::TailArgs),
+    /// Send a Prometheus Alertmanager webhook notification
+    Alertmanager(alertmanager::AlertmanagerArgs),
+    /// Inspect, replay or purge an outbox journal of payloads waiting to be sent
+    Outbox(outbox::OutboxArgs),
+}
+
+/// Where messages get sent
+#[derive(Args, Debug)]
+struct Target {
+    /// Webhook url to send to
+    #[arg(long, global = true, env = "SLACK_WEBHOOK_URL", hide_env_values = true)]
+    webhook: Option<WebhookUrl>,
+    /// Send with a profile from the config file, which takes precedence over `--webhook`
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Config file holding the profiles [default: `$SLACK_HOOK_CONFIG` or
+    /// `~/.config/slack-hook/config.toml`]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Print the JSON payload instead of sending it
+    #[arg(long, global = true)]
+    dry_run: bool,
+}
+
+impl Target {
+    fn sender(&self) -> Result<Sender> {
+        let profile = match &self.profile {
+            Some(name) => Some(self.load_config()?.profile(name)?.clone()),
+            None => None,
+        };
+
+        if self.dry_run {
+            return Ok(Sender::DryRun(
+                profile.as_ref().map(|profile| Box::new(profile.defaults())),
+            ));
+        }
+
+        let slack = match (profile, &self.webhook) {
+            (Some(profile), _) => Slack::from_profile(&profile)?,
+            (None, Some(webhook)) => Slack::new(webhook.clone())?,
+            (None, None) => {
+                return Err(Error::Config(
+                    "no webhook given, use `--webhook`, `SLACK_WEBHOOK_URL` or `--profile`"
+                        .to_owned(),
+                ))
+            }
+        };
+        Ok(Sender::Slack(Box::new(slack)))
+    }
+
+    fn load_config(&self) -> Result<Config> {
+        match &self.config {
+            Some(path) => Config::from_path(path),
+            None => Config::load(),
+        }
+    }
+}
+
+/// Sends payloads, or prints them on a dry run
+#[derive(Debug)]
+enum Sender {
+    Slack(Box<Slack>),
+    DryRun(Option<Box<Payload>>),
+}
+
+impl Sender {
+    fn send(&self, payload: &Payload) -> Result<()> {
+        match self {
+            Sender::Slack(slack) => slack.send(payload),
+            Sender::DryRun(defaults) => {
+                let payload = match defaults {
+                    Some(defaults) => payload.with_defaults(defaults),
+                    None => payload.clone(),
+                };
+                println!("{}", serde_json::to_string_pretty(&payload)?);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Read a file, where `-` is stdin
+fn read_input(path: &Path) -> Result<String> {
+    let mut contents = String::new();
+    if path == Path::new("-") {
+        let _ = io::stdin().read_to_string(&mut contents)?;
+    } else {
+        contents = fs::read_to_string(path)?;
+    }
+    Ok(contents)
+}
+
+/// Wrap command output in a code block
+fn code_block(lines: &[String]) -> String {
+    // a stray fence in the output would end the block early
+    let body = lines.join("\n").replace("```", "` ` `");
+    format!("```\n{body}\n```")
+}
+
+fn run(cli: Cli) -> Result<ExitCode> {
+    let target = &cli.target;
+    match cli.command {
+        Command::Send(args) => args.run(&target.sender()?),
+        Command::Run(args) => args.run(&target.sender()?),
+        Command::Tail(args) => args.run(&target.sender()?),
+        Command::Alertmanager(args) => args.run(&target.sender()?),
+        // listing and purging don't need a webhook
+        Command::Outbox(args) => args.run(target),
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("slack-hook: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{Sender, Target};
+use clap::{Args, Subcommand};
+use slack_hook::{
+    outbox::{Entry, Journal},
+    Error, Result,
+};
+use std::{path::PathBuf, process::ExitCode};
+
+#[derive(Args, Debug)]
+pub struct OutboxArgs {
+    /// Journal file the outbox keeps its payloads in
+    #[arg(long, env = "SLACK_HOOK_OUTBOX")]
+    journal: PathBuf,
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// List the payloads waiting to be sent, oldest first
+    List {
+        /// Print each entry as a line of JSON instead
+        #[arg(long)]
+        json: bool,
+        /// List the payloads slack rejected instead, along with why
+        #[arg(long)]
+        rejected: bool,
+    },
+    /// Send the waiting payloads in order, removing each one once it is delivered and setting
+    /// aside any slack rejects. Stop anything else sending through the journal first
+    Replay,
+    /// Remove waiting and rejected payloads without sending them. Stop anything else sending
+    /// through the journal first
+    Purge {
+        /// Only remove the entry with this id
+        #[arg(long)]
+        id: Option<u64>,
+    },
+}
+
+impl OutboxArgs {
+    pub fn run(&self, target: &Target) -> Result<ExitCode> {
+        // opening would create a missing journal, which only hides a mistyped path here
+        if !self.journal.is_file() {
+            return Err(Error::Config(format!(
+                "no outbox journal at `{}`",
+                self.journal.display()
+            )));
+        }
+        let journal = Journal::open(&self.journal)?;
+
+        match &self.action {
+            Action::List { json, rejected } => {
+                let entries = if *rejected {
+                    journal.rejected()?
+                } else {
+                    journal.entries()?
+                };
+                for entry in entries {
+                    if *json {
+                        println!("{}", serde_json::to_string(&entry)?);
+                    } else {
+                        println!("{}", summary(&entry));
+                    }
+                }
+            }
+            Action::Replay => {
+                let sender = target.sender()?;
+                if let Sender::DryRun(_) = sender {
+                    for entry in journal.entries()? {
+                        sender.send(&entry.payload)?;
+                    }
+                } else {
+                    let rejected = journal.rejected()?.len();
+                    let delivered = journal.replay(|payload| sender.send(payload))?;
+                    println!("delivered {delivered}");
+                    match journal.rejected()?.len() - rejected {
+                        0 => {}
+                        newly => println!("rejected {newly}, see `list --rejected`"),
+                    }
+                }
+            }
+            Action::Purge { id: Some(id) } => {
+                if !journal.remove(*id)? {
+                    return Err(Error::Config(format!("no outbox entry with id {id}")));
+                }
+                println!("removed {id}");
+            }
+            Action::Purge { id: None } => println!("purged {}", journal.purge()?),
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// The entry's id, when it was queued and the first line of its text, followed by why it was
+/// rejected if it was
+fn summary(entry: &Entry) -> String {
+    let payload = &entry.payload;
+    let text = payload
+        .text
+        .as_ref()
+        .or_else(|| Some(&payload.attachments.as_ref()?.first()?.fallback))
+        .map(ToString::to_string)
+        .unwrap_or_default();
+    let first_line = text.lines().next().unwrap_or_default();
+    let summary = format!(
+        "{}\t{}\t{first_line}",
+        entry.id,
+        entry.queued_at.timestamp()
+    );
+    match &entry.rejected {
+        Some(reason) => format!("{summary}\t{reason}"),
+        None => summary,
+    }
+}
@@ -0,0 +1,326 @@
+use crate::{code_block, Sender};
+use clap::Args;
+use slack_hook::{
+    AttachmentBuilder, Field, Payload, PayloadBuilder, Result, Section, SlackColor, SlackTime,
+};
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{Command, ExitCode, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Exit code used when the command couldn't be started, matching what shells use
+const NOT_STARTED_EXIT_CODE: u8 = 127;
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// How many trailing lines of stdout and stderr to include
+    #[arg(long, default_value = "20")]
+    lines: usize,
+    /// Name used for the command in the message [default: the command line]
+    #[arg(long)]
+    name: Option<String>,
+    /// Only send a message when the command fails
+    #[arg(long)]
+    only_failures: bool,
+    /// The command to run along with its arguments
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+}
+
+impl RunArgs {
+    pub fn run(&self, sender: &Sender) -> Result<ExitCode> {
+        let outcome = self.execute();
+        if !(self.only_failures && outcome.status.success()) {
+            let sent = outcome
+                .payload(self.name.as_deref())
+                .and_then(|payload| sender.send(&payload));
+            // the command's status is what callers check, so a failed report doesn't replace it
+            if let Err(err) = sent {
+                eprintln!("slack-hook: couldn't report `{}`: {err}", outcome.command);
+            }
+        }
+        Ok(outcome.status.exit_code())
+    }
+
+    fn execute(&self) -> Outcome {
+        let started = Instant::now();
+        let (program, args) = self.command.split_first().expect("clap requires a command");
+
+        let mut child = match Command::new(program)
+            .args(args)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) => {
+                return Outcome {
+                    command: self.command.join(" "),
+                    status: Status::NotStarted(err.to_string()),
+                    duration: started.elapsed(),
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                }
+            }
+        };
+
+        let lines = self.lines;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let stdout = thread::spawn(move || tee(stdout, io::stdout(), lines));
+        let stderr = thread::spawn(move || tee(stderr, io::stderr(), lines));
+
+        let status = match child.wait() {
+            Ok(status) => Status::from(status),
+            Err(err) => Status::NotStarted(err.to_string()),
+        };
+        Outcome {
+            command: self.command.join(" "),
+            status,
+            duration: started.elapsed(),
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        }
+    }
+}
+
+/// Pass the output through while keeping the last `lines` lines of it
+fn tee<R: Read, W: Write>(output: R, mut passthrough: W, lines: usize) -> Vec<String> {
+    let mut tail = VecDeque::with_capacity(lines);
+    for line in BufReader::new(output).split(b'\n') {
+        let Ok(line) = line else { break };
+        // the passthrough is best effort, e.g. our own stdout may be closed
+        let _ = passthrough.write_all(&line);
+        let _ = passthrough.write_all(b"\n");
+        if lines == 0 {
+            continue;
+        }
+        if tail.len() == lines {
+            let _ = tail.pop_front();
+        }
+        tail.push_back(String::from_utf8_lossy(&line).into_owned());
+    }
+    tail.into()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Status {
+    Exited(i32),
+    Signaled(i32),
+    NotStarted(String),
+}
+
+impl From<ExitStatus> for Status {
+    fn from(status: ExitStatus) -> Status {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return Status::Signaled(signal);
+            }
+        }
+        Status::Exited(status.code().unwrap_or(1))
+    }
+}
+
+impl Status {
+    fn success(&self) -> bool {
+        *self == Status::Exited(0)
+    }
+
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Status::Exited(code) => ExitCode::from(u8::try_from(*code).unwrap_or(1)),
+            // mirror the shell convention of 128 + signal
+            Status::Signaled(signal) => ExitCode::from(u8::try_from(128 + signal).unwrap_or(1)),
+            Status::NotStarted(_) => ExitCode::from(NOT_STARTED_EXIT_CODE),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Status::Exited(code) => format!("exit code {code}"),
+            Status::Signaled(signal) => format!("killed by signal {signal}"),
+            Status::NotStarted(err) => format!("failed to start: {err}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Outcome {
+    command: String,
+    status: Status,
+    duration: Duration,
+    stdout: Vec<String>,
+    stderr: Vec<String>,
+}
+
+impl Outcome {
+    fn payload(&self, name: Option<&str>) -> Result<Payload> {
+        let name = name.unwrap_or(&self.command);
+        let (color, verb) = if self.status.success() {
+            (SlackColor::Good, "succeeded")
+        } else {
+            (SlackColor::Danger, "failed")
+        };
+        let summary = format!("{name} {verb}");
+
+        let mut fields = vec![
+            Field::new("Status", self.status.describe(), Some(true)),
+            Field::new("Duration", format_duration(self.duration), Some(true)),
+        ];
+        if name != self.command {
+            fields.push(Field::new("Command", self.command.as_str(), None));
+        }
+
+        let mut builder = AttachmentBuilder::new(summary.as_str())
+            .title(summary.as_str())
+            .color(color)
+            .fields(fields)
+            .footer("slack-hook run")
+            .ts(SlackTime::now());
+        let output = [("stdout", &self.stdout), ("stderr", &self.stderr)]
+            .iter()
+            .filter(|(_, lines)| !lines.is_empty())
+            .map(|(stream, lines)| format!("*{stream}*\n{}", code_block(lines)))
+            .collect::<Vec<_>>();
+        if !output.is_empty() {
+            builder = builder
+                .text(output.join("\n"))
+                .markdown_in(&[Section::Text]);
+        }
+
+        PayloadBuilder::new()
+            .attachments(vec![builder.build()?])
+            .build()
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, _) => format!("{:.1}s", duration.as_secs_f64()),
+        (0, m, s) => format!("{m}m {s}s"),
+        (h, m, s) => format!("{h}h {m}m {s}s"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::{assert_json_snapshot, assert_snapshot};
+    use slack_hook::{blocking::Slack, Dialect, WebhookUrl};
+
+    #[test]
+    fn tail_keeps_last_lines() {
+        let mut passthrough = Vec::new();
+        let tail = tee(&b"one\ntwo\nthree\n"[..], &mut passthrough, 2);
+        assert_eq!(tail, ["two", "three"]);
+        assert_eq!(passthrough, b"one\ntwo\nthree\n");
+    }
+
+    #[test]
+    fn durations() {
+        assert_snapshot!(format_duration(Duration::from_millis(1_250)), @"1.2s");
+        assert_snapshot!(format_duration(Duration::from_secs(125)), @"2m 5s");
+        assert_snapshot!(format_duration(Duration::from_secs(3_725)), @"1h 2m 5s");
+    }
+
+    #[test]
+    fn failure_payload() {
+        let outcome = Outcome {
+            command: "make release".to_owned(),
+            status: Status::Exited(2),
+            duration: Duration::from_secs(83),
+            stdout: vec!["building...".to_owned()],
+            stderr: vec!["error: <oops>".to_owned()],
+        };
+        let mut payload = outcome.payload(Some("release")).unwrap();
+        let attachment = &mut payload.attachments.as_mut().unwrap()[0];
+        assert!(attachment.ts.take().is_some());
+        assert_json_snapshot!(payload, @r###"
+        {
+          "attachments": [
+            {
+              "fallback": "release failed",
+              "text": "*stdout*\n```\nbuilding...\n```\n*stderr*\n```\nerror: &lt;oops&gt;\n```",
+              "color": "danger",
+              "fields": [
+                {
+                  "title": "Status",
+                  "value": "exit code 2",
+                  "short": true
+                },
+                {
+                  "title": "Duration",
+                  "value": "1m 23s",
+                  "short": true
+                },
+                {
+                  "title": "Command",
+                  "value": "make release"
+                }
+              ],
+              "title": "release failed",
+              "footer": "slack-hook run",
+              "mrkdwn_in": [
+                "text"
+              ]
+            }
+          ]
+        }
+        "###);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_command() {
+        let args = RunArgs {
+            lines: 1,
+            name: None,
+            only_failures: false,
+            command: ["sh", "-c", "echo one; echo two; echo err >&2; exit 3"]
+                .map(String::from)
+                .to_vec(),
+        };
+        let outcome = args.execute();
+        assert_eq!(outcome.status, Status::Exited(3));
+        assert_eq!(outcome.stdout, ["two"]);
+        assert_eq!(outcome.stderr, ["err"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_status_when_report_fails() {
+        let hook = WebhookUrl::parse_dialect("https://127.0.0.1:1/hooks/key", Dialect::Mattermost);
+        let sender = Sender::Slack(Box::new(Slack::new(hook.unwrap()).unwrap()));
+        let args = RunArgs {
+            lines: 1,
+            name: None,
+            only_failures: false,
+            command: ["sh", "-c", "exit 3"].map(String::from).to_vec(),
+        };
+        assert_eq!(args.run(&sender).unwrap(), ExitCode::from(3));
+    }
+
+    #[test]
+    fn missing_command() {
+        let args = RunArgs {
+            lines: 1,
+            name: None,
+            only_failures: false,
+            command: vec!["slack-hook-this-command-does-not-exist".to_owned()],
+        };
+        let outcome = args.execute();
+        assert!(matches!(outcome.status, Status::NotStarted(_)));
+        assert_eq!(
+            outcome.status.exit_code(),
+            ExitCode::from(NOT_STARTED_EXIT_CODE)
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{read_input, Sender};
+use clap::Args;
+use slack_hook::{AttachmentBuilder, Error, Field, HexColor, Payload, PayloadBuilder, Result};
+use std::{path::PathBuf, process::ExitCode};
+
+#[derive(Args, Debug)]
+pub struct SendArgs {
+    /// Message text, or `-` to read it from stdin. With any attachment options the text goes in
+    /// the attachment
+    #[arg(required_unless_present_any = ["json", "title", "fields"])]
+    text: Option<String>,
+    /// Send the JSON payload from a file instead, or `-` to read it from stdin
+    #[arg(long, conflicts_with_all = ["text", "color", "title", "title_link", "fields", "footer"])]
+    json: Option<PathBuf>,
+    /// Channel override
+    #[arg(long)]
+    channel: Option<String>,
+    /// Username override
+    #[arg(long)]
+    username: Option<String>,
+    /// Icon emoji override, e.g. `:rocket:`
+    #[arg(long)]
+    icon_emoji: Option<String>,
+    /// Icon url override
+    #[arg(long)]
+    icon_url: Option<String>,
+    #[command(flatten)]
+    attachment: AttachmentArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AttachmentArgs {
+    /// Attachment color: `good`, `warning`, `danger`, a hex code or a CSS color name
+    #[arg(long)]
+    color: Option<HexColor>,
+    /// Attachment title
+    #[arg(long)]
+    title: Option<String>,
+    /// Url the attachment title links to
+    #[arg(long, requires = "title")]
+    title_link: Option<String>,
+    /// Attachment field as `title=value`, can be repeated
+    #[arg(long = "field", value_parser = parse_field)]
+    fields: Vec<Field>,
+    /// Display the fields side-by-side
+    #[arg(long, requires = "fields")]
+    short: bool,
+    /// Attachment footer
+    #[arg(long)]
+    footer: Option<String>,
+}
+
+impl AttachmentArgs {
+    fn is_empty(&self) -> bool {
+        self.color.is_none()
+            && self.title.is_none()
+            && self.fields.is_empty()
+            && self.footer.is_none()
+    }
+
+    /// Build the attachment, falling back to the title for clients that can't display it
+    fn build(&self, text: Option<String>) -> Result<slack_hook::Attachment> {
+        let fallback = text
+            .clone()
+            .or_else(|| self.title.clone())
+            .unwrap_or_else(|| "attachment".to_owned());
+        let mut builder = AttachmentBuilder::new(fallback);
+        if let Some(text) = text {
+            builder = builder.text(text);
+        }
+        if let Some(color) = self.color {
+            builder = builder.color(color);
+        }
+        if let Some(title) = &self.title {
+            builder = builder.title(title.as_str());
+        }
+        if let Some(title_link) = &self.title_link {
+            builder = builder.title_link(title_link);
+        }
+        if !self.fields.is_empty() {
+            let short = Some(self.short).filter(|short| *short);
+            let fields = self
+                .fields
+                .iter()
+                .cloned()
+                .map(|field| Field { short, ..field })
+                .collect();
+            builder = builder.fields(fields);
+        }
+        if let Some(footer) = &self.footer {
+            builder = builder.footer(footer.as_str());
+        }
+        builder.build()
+    }
+}
+
+impl SendArgs {
+    pub fn payload(&self) -> Result<Payload> {
+        let mut payload = match &self.json {
+            Some(path) => serde_json::from_str(&read_input(path)?)?,
+            None => {
+                let text = match self.text.as_deref() {
+                    Some("-") => Some(read_input("-".as_ref())?.trim_end().to_owned()),
+                    text => text.map(str::to_owned),
+                };
+                let builder = PayloadBuilder::new();
+                let builder = if !self.attachment.is_empty() {
+                    builder.attachments(vec![self.attachment.build(text)?])
+                } else if let Some(text) = text {
+                    builder.text(text)
+                } else {
+                    return Err(Error::Config("nothing to send".to_owned()));
+                };
+                builder.build()?
+            }
+        };
+
+        if let Some(channel) = &self.channel {
+            payload.channel = Some(channel.clone());
+        }
+        if let Some(username) = &self.username {
+            payload.username = Some(username.clone());
+        }
+        if let Some(icon_emoji) = &self.icon_emoji {
+            payload.icon_emoji = Some(icon_emoji.clone());
+        }
+        if let Some(icon_url) = &self.icon_url {
+            payload.icon_url = Some(icon_url.parse()?);
+        }
+        Ok(payload)
+    }
+
+    pub fn run(&self, sender: &Sender) -> Result<ExitCode> {
+        sender.send(&self.payload()?)?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn parse_field(s: &str) -> std::result::Result<Field, String> {
+    match s.split_once('=') {
+        Some((title, value)) => Ok(Field::new(title, value, None)),
+        None => Err(format!("expected `title=value`, found `{s}`")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Cli, Command};
+    use clap::Parser;
+    use insta::{assert_json_snapshot, assert_snapshot};
+
+    fn payload(args: &[&str]) -> slack_hook::Result<slack_hook::Payload> {
+        let cli = Cli::try_parse_from(["slack-hook", "send"].iter().chain(args)).unwrap();
+        match cli.command {
+            Command::Send(send) => send.payload(),
+            command => panic!("expected send, found {:?}", command),
+        }
+    }
+
+    #[test]
+    fn text() {
+        let p = payload(&["deploy <done> & dusted", "--channel", "#deploys"]).unwrap();
+        assert_json_snapshot!(p, @r###"
+        {
+          "text": "deploy &lt;done&gt; &amp; dusted",
+          "channel": "#deploys"
+        }
+        "###);
+    }
+
+    #[test]
+    fn attachment() {
+        let p = payload(&[
+            "backup finished",
+            "--color",
+            "good",
+            "--title",
+            "Nightly backup",
+            "--field",
+            "size=12 GB",
+            "--field",
+            "duration=3m",
+            "--short",
+            "--footer",
+            "cron",
+        ])
+        .unwrap();
+        assert_json_snapshot!(p, @r###"
+        {
+          "attachments": [
+            {
+              "fallback": "backup finished",
+              "text": "backup finished",
+              "color": "good",
+              "fields": [
+                {
+                  "title": "size",
+                  "value": "12 GB",
+                  "short": true
+                },
+                {
+                  "title": "duration",
+                  "value": "3m",
+                  "short": true
+                }
+              ],
+              "title": "Nightly backup",
+              "footer": "cron"
+            }
+          ]
+        }
+        "###);
+    }
+
+    #[test]
+    fn bad_field() {
+        let err = Cli::try_parse_from(["slack-hook", "send", "--field", "oops"]).unwrap_err();
+        assert_snapshot!(
+            err.to_string().lines().next().unwrap(),
+            @"error: invalid value 'oops' for '--field <FIELDS>': expected `title=value`, found `oops`"
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use crate::{code_block, Sender};
+use clap::Args;
+use regex::Regex;
+use slack_hook::{Error, PayloadBuilder, Result};
+use std::{
+    io::{self, BufRead},
+    mem,
+    process::ExitCode,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Slack allows about one message per second through a webhook
+const MIN_SEND_GAP: Duration = Duration::from_secs(1);
+
+/// Most times a batch is resent after slack rate limits it
+const MAX_RATE_LIMITED: usize = 3;
+
+/// Longest `--interval` accepted, a day
+const MAX_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Room left in a message for the code fences
+const FENCE_LEN: usize = "```\n\n```".len();
+
+#[derive(Args, Debug)]
+pub struct TailArgs {
+    /// Seconds to wait for more lines before sending a batch, at most a day
+    #[arg(long, default_value = "2", value_parser = parse_secs)]
+    interval: Duration,
+    /// Most lines sent in one message
+    #[arg(long, default_value = "50")]
+    max_lines: usize,
+    /// Most bytes sent in one message, longer lines are truncated
+    #[arg(long, default_value = "3500")]
+    max_bytes: usize,
+    /// Only send lines matching this regex
+    #[arg(long)]
+    filter: Option<Regex>,
+}
+
+impl TailArgs {
+    pub fn run(&self, sender: &Sender) -> Result<ExitCode> {
+        let (lines_tx, lines_rx) = mpsc::channel();
+        let _reader = thread::spawn(move || {
+            for line in io::stdin().lock().split(b'\n') {
+                let line = line.map(|line| String::from_utf8_lossy(&line).into_owned());
+                if lines_tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut batcher = Batcher::new(self.max_lines, self.max_bytes.saturating_sub(FENCE_LEN));
+        let mut limiter = RateLimiter::new(MIN_SEND_GAP);
+        let mut failed = false;
+        // when the current batch gets sent even if it isn't full
+        let mut deadline: Option<Instant> = None;
+        loop {
+            let line = match deadline {
+                Some(deadline) => {
+                    lines_rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => lines_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let batch = match line {
+                Ok(Err(err)) => {
+                    let _ = self.send(sender, &mut limiter, batcher.take());
+                    return Err(err.into());
+                }
+                Ok(Ok(line)) => {
+                    if !self.filter.as_ref().map_or(true, |re| re.is_match(&line)) {
+                        continue;
+                    }
+                    if batcher.is_empty() {
+                        deadline = Instant::now().checked_add(self.interval);
+                    }
+                    match batcher.push(line) {
+                        Some(batch) => batch,
+                        None => continue,
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => batcher.take(),
+                Err(RecvTimeoutError::Disconnected) => {
+                    failed |= !self.send(sender, &mut limiter, batcher.take());
+                    break;
+                }
+            };
+            deadline = if batcher.is_empty() {
+                None
+            } else {
+                Instant::now().checked_add(self.interval)
+            };
+            failed |= !self.send(sender, &mut limiter, batch);
+        }
+
+        Ok(if failed {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+
+    /// Send a batch, reporting rather than returning errors so one failure doesn't stop the stream
+    fn send(&self, sender: &Sender, limiter: &mut RateLimiter, batch: Vec<String>) -> bool {
+        if batch.is_empty() {
+            return true;
+        }
+        let result = PayloadBuilder::new()
+            .text(code_block(&batch))
+            .build()
+            .and_then(|payload| {
+                let mut rate_limited = 0;
+                loop {
+                    limiter.wait();
+                    match sender.send(&payload) {
+                        Err(Error::RateLimited(retry_after)) if rate_limited < MAX_RATE_LIMITED => {
+                            rate_limited += 1;
+                            thread::sleep(retry_after.unwrap_or(MIN_SEND_GAP));
+                        }
+                        result => return result,
+                    }
+                }
+            });
+        match result {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("slack-hook: failed sending {} lines: {err}", batch.len());
+                false
+            }
+        }
+    }
+}
+
+fn parse_secs(s: &str) -> std::result::Result<Duration, String> {
+    let secs = match s.parse::<f64>() {
+        Ok(secs) if secs >= 0.0 => secs,
+        _ => return Err(format!("expected a number of seconds, found `{s}`")),
+    };
+    match Duration::try_from_secs_f64(secs) {
+        Ok(interval) if interval <= MAX_INTERVAL => Ok(interval),
+        _ => Err(format!(
+            "expected at most {} seconds, found `{s}`",
+            MAX_INTERVAL.as_secs()
+        )),
+    }
+}
+
+/// Collects lines until a batch is full, measuring them as they will be sent
+#[derive(Debug)]
+struct Batcher {
+    max_lines: usize,
+    max_bytes: usize,
+    lines: Vec<String>,
+    bytes: usize,
+}
+
+impl Batcher {
+    fn new(max_lines: usize, max_bytes: usize) -> Batcher {
+        Batcher {
+            max_lines: max_lines.max(1),
+            max_bytes: max_bytes.max(1),
+            lines: Vec::new(),
+            bytes: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Add a line, returning the previous batch when the line doesn't fit in it
+    fn push(&mut self, mut line: String) -> Option<Vec<String>> {
+        truncate(&mut line, self.max_bytes);
+        let len = encoded_len(&line);
+        // lines after the first are joined with a newline
+        let full = if !self.is_empty() && self.bytes + 1 + len > self.max_bytes {
+            Some(self.take())
+        } else {
+            None
+        };
+        if !self.is_empty() {
+            self.bytes += 1;
+        }
+        self.bytes += len;
+        self.lines.push(line);
+
+        full.or_else(|| {
+            if self.lines.len() >= self.max_lines {
+                Some(self.take())
+            } else {
+                None
+            }
+        })
+    }
+
+    fn take(&mut self) -> Vec<String> {
+        self.bytes = 0;
+        mem::take(&mut self.lines)
+    }
+}
+
+/// The length of `line` once its fences are broken up and slack's control characters escaped
+fn encoded_len(line: &str) -> usize {
+    let escapes: usize = line
+        .bytes()
+        .map(|b| match b {
+            b'&' => "&amp;".len() - 1,
+            b'<' | b'>' => "&lt;".len() - 1,
+            _ => 0,
+        })
+        .sum();
+    line.len() + escapes + line.matches("```").count() * ("` ` `".len() - 3)
+}
+
+/// Shorten `s` to at most `max` encoded bytes without splitting a character
+fn truncate(s: &mut String, max: usize) {
+    if s.len() > max {
+        let end = (0..=max)
+            .rev()
+            .find(|i| s.is_char_boundary(*i))
+            .unwrap_or(0);
+        s.truncate(end);
+    }
+    while encoded_len(s) > max {
+        let _ = s.pop();
+    }
+}
+
+/// Spaces out sends to stay under the webhook's rate limit
+#[derive(Debug)]
+struct RateLimiter {
+    gap: Duration,
+    last: Option<Instant>,
+}
+
+impl RateLimiter {
+    fn new(gap: Duration) -> RateLimiter {
+        RateLimiter { gap, last: None }
+    }
+
+    fn wait(&mut self) {
+        if let Some(last) = self.last {
+            thread::sleep(self.gap.saturating_sub(last.elapsed()));
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    fn lines(batch: Option<Vec<String>>) -> Option<String> {
+        batch.map(|batch| batch.join("|"))
+    }
+
+    #[test]
+    fn batch_by_lines() {
+        let mut batcher = Batcher::new(2, 100);
+        assert_eq!(lines(batcher.push("one".to_owned())), None);
+        assert_eq!(lines(batcher.push("two".to_owned())).unwrap(), "one|two");
+        assert!(batcher.is_empty());
+        assert_eq!(lines(batcher.push("three".to_owned())), None);
+        assert_eq!(batcher.take(), ["three"]);
+    }
+
+    #[test]
+    fn batch_by_bytes() {
+        let mut batcher = Batcher::new(10, 8);
+        assert_eq!(lines(batcher.push("abc".to_owned())), None);
+        assert_eq!(lines(batcher.push("def".to_owned())), None);
+        assert_eq!(lines(batcher.push("ghi".to_owned())).unwrap(), "abc|def");
+        assert_eq!(batcher.take(), ["ghi"]);
+    }
+
+    #[test]
+    fn long_lines_are_truncated() {
+        let mut batcher = Batcher::new(10, 4);
+        assert_eq!(lines(batcher.push("aé€xyz".to_owned())), None);
+        assert_eq!(batcher.take(), ["aé"]);
+    }
+
+    #[test]
+    fn batch_by_encoded_bytes() {
+        let mut batcher = Batcher::new(10, 12);
+        assert_eq!(lines(batcher.push("a<b".to_owned())), None);
+        assert_eq!(lines(batcher.push("a&b".to_owned())).unwrap(), "a<b");
+        assert_eq!(lines(batcher.push("<<<<".to_owned())).unwrap(), "a&b");
+        assert_eq!(lines(batcher.push("```x```".to_owned())).unwrap(), "<<<");
+        assert_eq!(batcher.take(), ["```x```"]);
+
+        let mut batcher = Batcher::new(10, 10);
+        assert_eq!(lines(batcher.push("&&&".to_owned())), None);
+        assert_eq!(batcher.take(), ["&&"]);
+    }
+
+    #[test]
+    fn fences_are_broken_up() {
+        let batch = ["```rust".to_owned(), "<tag>".to_owned()];
+        assert_snapshot!(code_block(&batch), @r###"
+        ```
+        ` ` `rust
+        <tag>
+        ```
+        "###);
+    }
+
+    #[test]
+    fn bad_interval() {
+        assert_snapshot!(parse_secs("-1").unwrap_err(), @"expected a number of seconds, found `-1`");
+        assert_eq!(parse_secs("0.5").unwrap(), Duration::from_millis(500));
+        assert_snapshot!(parse_secs("1e20").unwrap_err(), @"expected at most 86400 seconds, found `1e20`");
+        assert_snapshot!(parse_secs("inf").unwrap_err(), @"expected at most 86400 seconds, found `inf`");
+        assert!(parse_secs("NaN").is_err());
+        assert_eq!(parse_secs("86400").unwrap(), MAX_INTERVAL);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::SlackText;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+mod legacy;
+
+pub use self::legacy::{BlockMigration, FidelityLoss};
+
+/// A Block Kit layout block. See <https://api.slack.com/reference/block-kit/blocks> for more
+/// information.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    /// Text, optionally laid out alongside fields and an accessory element
+    Section {
+        /// Main text of the section
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<TextObject>,
+        /// Shown in two columns, at most 10 of them
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fields: Option<Vec<TextObject>>,
+        /// An element shown to the right of the text
+        #[serde(skip_serializing_if = "Option::is_none")]
+        accessory: Option<BlockElement>,
+    },
+    /// Small, muted text and images
+    Context {
+        /// The text and images, at most 10 of them
+        elements: Vec<ContextElement>,
+    },
+    /// A standalone image
+    Image {
+        /// Url of the image
+        image_url: Url,
+        /// Plain text summary of the image
+        alt_text: String,
+        /// Optional title shown above the image
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<TextObject>,
+    },
+    /// Interactive elements like buttons
+    Actions {
+        /// The elements, at most 25 of them
+        elements: Vec<BlockElement>,
+    },
+    /// A thin line separating blocks
+    Divider,
+    /// Large, bold text
+    Header {
+        /// The text, which must be plain
+        text: TextObject,
+    },
+}
+
+/// Text used within blocks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextObject {
+    /// Text shown as-is
+    PlainText {
+        /// The text
+        text: SlackText,
+    },
+    /// Text formatted with slack's markdown
+    Mrkdwn {
+        /// The text
+        text: SlackText,
+    },
+}
+
+impl TextObject {
+    /// Construct plain text
+    pub fn plain<S: Into<SlackText>>(text: S) -> TextObject {
+        TextObject::PlainText { text: text.into() }
+    }
+
+    /// Construct markdown formatted text
+    pub fn mrkdwn<S: Into<SlackText>>(text: S) -> TextObject {
+        TextObject::Mrkdwn { text: text.into() }
+    }
+}
+
+/// Elements used in section accessories and action blocks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockElement {
+    /// A small image
+    Image {
+        /// Url of the image
+        image_url: Url,
+        /// Plain text summary of the image
+        alt_text: String,
+    },
+    /// A button that sends an interaction to the app, or opens a url
+    Button {
+        /// Label of the button, which must be plain
+        text: TextObject,
+        /// Identifies the button in the interaction sent to the app
+        #[serde(skip_serializing_if = "Option::is_none")]
+        action_id: Option<String>,
+        /// Url opened when the button is clicked
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<Url>,
+        /// Value sent along with the interaction
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+        /// Color of the button
+        #[serde(skip_serializing_if = "Option::is_none")]
+        style: Option<ButtonStyle>,
+    },
+}
+
+/// How a button is decorated
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ButtonStyle {
+    /// Green, for the affirmative action
+    Primary,
+    /// Red, for destructive actions
+    Danger,
+}
+
+/// Elements of a [`Block::Context`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContextElement {
+    /// Text shown as-is
+    PlainText {
+        /// The text
+        text: SlackText,
+    },
+    /// Text formatted with slack's markdown
+    Mrkdwn {
+        /// The text
+        text: SlackText,
+    },
+    /// A small image
+    Image {
+        /// Url of the image
+        image_url: Url,
+        /// Plain text summary of the image
+        alt_text: String,
+    },
+}
@@ -0,0 +1,402 @@
+//! Migrating legacy attachments to blocks
+
+use super::{Block, BlockElement, ButtonStyle, ContextElement, TextObject};
+use crate::{Attachment, HexColor, Section, SlackLink, SlackText};
+
+/// Most fields slack shows in a single section
+const MAX_SECTION_FIELDS: usize = 10;
+
+/// The blocks converted from an [`Attachment`], along with anything they couldn't carry over
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockMigration {
+    /// Blocks laid out like the attachment
+    pub blocks: Vec<Block>,
+    /// Parts of the attachment that the blocks don't show
+    pub losses: Vec<FidelityLoss>,
+}
+
+impl BlockMigration {
+    /// Whether the blocks show everything the attachment did
+    pub fn is_lossless(&self) -> bool {
+        self.losses.is_empty()
+    }
+}
+
+/// Something an attachment shows that its blocks can't
+#[derive(Debug, Clone, PartialEq)]
+pub enum FidelityLoss {
+    /// Blocks have no colored bar down their side
+    Color(HexColor),
+    /// Blocks have no fallback of their own, so set it as the payload's `text` instead
+    Fallback(SlackText),
+    /// Fields that weren't `short` are still laid out in two columns
+    WideFields,
+    /// An action other than a button, which has no block equivalent. Holds the action's name
+    Action(String),
+    /// Interactions with blocks don't carry a `callback_id`, each button's `action_id` is set to
+    /// the action's name instead
+    CallbackId(SlackText),
+}
+
+impl Attachment {
+    /// Convert this legacy attachment into equivalent Block Kit blocks
+    ///
+    /// The pretext, title and text become sections, as do the fields, with the thumbnail as the
+    /// first section's accessory. The author, footer and timestamp become context blocks and
+    /// button actions become an actions block. Anything that can't be converted is listed in
+    /// [`BlockMigration::losses`].
+    pub fn to_blocks(&self) -> BlockMigration {
+        let mut blocks = Vec::new();
+        let mut losses = Vec::new();
+        let markdown = |section| {
+            self.mrkdwn_in
+                .as_ref()
+                .map_or(false, |sections| sections.contains(&section))
+        };
+        let text_object = |text: &SlackText, section| {
+            if markdown(section) {
+                TextObject::mrkdwn(text.clone())
+            } else {
+                TextObject::plain(text.clone())
+            }
+        };
+
+        if let Some(pretext) = &self.pretext {
+            blocks.push(section(text_object(pretext, Section::Pretext)));
+        }
+
+        if let Some(author_name) = &self.author_name {
+            let mut elements = Vec::new();
+            if let Some(icon) = &self.author_icon {
+                elements.push(ContextElement::Image {
+                    image_url: icon.clone(),
+                    alt_text: "author icon".to_owned(),
+                });
+            }
+            let author = match &self.author_link {
+                Some(link) => linked(author_name, link.as_str()),
+                None => author_name.clone(),
+            };
+            elements.push(ContextElement::Mrkdwn { text: author });
+            blocks.push(Block::Context { elements });
+        }
+
+        // the thumbnail goes next to the first section holding the attachment's content
+        let content_start = blocks.len();
+        if let Some(title) = &self.title {
+            let title = match &self.title_link {
+                Some(link) => linked(title, link.as_str()),
+                None => title.clone(),
+            };
+            blocks.push(section(TextObject::mrkdwn(SlackText::new_raw(format!(
+                "*{title}*"
+            )))));
+        }
+        if let Some(text) = &self.text {
+            blocks.push(section(text_object(text, Section::Text)));
+        }
+
+        if let Some(fields) = self.fields.as_ref().filter(|fields| !fields.is_empty()) {
+            if fields.iter().any(|field| field.short != Some(true)) {
+                losses.push(FidelityLoss::WideFields);
+            }
+            for chunk in fields.chunks(MAX_SECTION_FIELDS) {
+                let fields = chunk
+                    .iter()
+                    .map(|field| {
+                        TextObject::mrkdwn(SlackText::new_raw(format!(
+                            "*{}*\n{}",
+                            SlackText::new(field.title.as_str()),
+                            field.value
+                        )))
+                    })
+                    .collect();
+                blocks.push(Block::Section {
+                    text: None,
+                    fields: Some(fields),
+                    accessory: None,
+                });
+            }
+        }
+
+        if let Some(thumb_url) = &self.thumb_url {
+            let thumbnail = BlockElement::Image {
+                image_url: thumb_url.clone(),
+                alt_text: "thumbnail".to_owned(),
+            };
+            match blocks.get_mut(content_start) {
+                Some(Block::Section { accessory, .. }) => *accessory = Some(thumbnail),
+                _ => blocks.push(Block::Image {
+                    image_url: thumb_url.clone(),
+                    alt_text: "thumbnail".to_owned(),
+                    title: None,
+                }),
+            }
+        }
+
+        if let Some(image_url) = &self.image_url {
+            blocks.push(Block::Image {
+                image_url: image_url.clone(),
+                alt_text: "image".to_owned(),
+                title: None,
+            });
+        }
+
+        if let Some(actions) = &self.actions {
+            let mut buttons = Vec::new();
+            for action in actions {
+                if action.action_type != "button" {
+                    losses.push(FidelityLoss::Action(action.name.clone()));
+                    continue;
+                }
+                buttons.push(BlockElement::Button {
+                    text: TextObject::plain(action.text.as_str()),
+                    action_id: Some(action.name.clone()),
+                    url: None,
+                    value: action.value.clone(),
+                    style: match action.style.as_deref() {
+                        Some("primary") => Some(ButtonStyle::Primary),
+                        Some("danger") => Some(ButtonStyle::Danger),
+                        _ => None,
+                    },
+                });
+            }
+            if !buttons.is_empty() {
+                blocks.push(Block::Actions { elements: buttons });
+            }
+        }
+
+        let date = self.ts.map(|ts| {
+            let secs = ts.timestamp();
+            format!("<!date^{secs}^{{date_short_pretty}} at {{time}}|{secs}>")
+        });
+        let footer = match (&self.footer, date) {
+            (Some(footer), Some(date)) => Some(SlackText::new_raw(format!("{footer} | {date}"))),
+            (Some(footer), None) => Some(footer.clone()),
+            (None, Some(date)) => Some(SlackText::new_raw(date)),
+            (None, None) => None,
+        };
+        if let Some(footer) = footer {
+            let mut elements = Vec::new();
+            if let Some(icon) = &self.footer_icon {
+                elements.push(ContextElement::Image {
+                    image_url: icon.clone(),
+                    alt_text: "footer icon".to_owned(),
+                });
+            }
+            elements.push(ContextElement::Mrkdwn { text: footer });
+            blocks.push(Block::Context { elements });
+        }
+
+        if let Some(color) = self.color {
+            losses.push(FidelityLoss::Color(color));
+        }
+        let shown = [&self.title, &self.text];
+        if !self.fallback.to_string().is_empty()
+            && !shown
+                .iter()
+                .any(|text| text.as_ref() == Some(&self.fallback))
+        {
+            losses.push(FidelityLoss::Fallback(self.fallback.clone()));
+        }
+        if let Some(callback_id) = &self.callback_id {
+            losses.push(FidelityLoss::CallbackId(callback_id.clone()));
+        }
+
+        BlockMigration { blocks, losses }
+    }
+}
+
+fn section(text: TextObject) -> Block {
+    Block::Section {
+        text: Some(text),
+        fields: None,
+        accessory: None,
+    }
+}
+
+fn linked(text: &SlackText, url: &str) -> SlackText {
+    let link = SlackLink {
+        url: url.to_owned(),
+        text: text.clone(),
+    };
+    SlackText::new_raw(link.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Action, AttachmentBuilder, Field, SlackColor, SlackTime};
+    use insta::{assert_debug_snapshot, assert_json_snapshot};
+
+    #[test]
+    fn full_attachment() {
+        let attachment = AttachmentBuilder::new("Deploy finished")
+            .pretext("Heads up")
+            .author_name("ci <bot>")
+            .author_link("https://ci.example.com")
+            .author_icon("https://ci.example.com/icon.png")
+            .title("Deploy #42")
+            .title_link("https://ci.example.com/42")
+            .text("Rolled out *v1.2.3*")
+            .markdown_in(&[Section::Text])
+            .fields(vec![
+                Field::new("env", "prod", Some(true)),
+                Field::new("region", "us-east-1", Some(true)),
+            ])
+            .thumb_url("https://example.com/thumb.png")
+            .actions(vec![Action::new(
+                "button",
+                "Roll back",
+                "rollback",
+                Some("danger".to_owned()),
+                Some("42".to_owned()),
+            )])
+            .footer("ci")
+            .ts(SlackTime::from_unix(1_714_564_800, 0))
+            .build()
+            .unwrap();
+
+        let migration = attachment.to_blocks();
+        assert_eq!(
+            migration.losses,
+            [FidelityLoss::Fallback("Deploy finished".into())]
+        );
+        assert_json_snapshot!(migration.blocks, @r###"
+        [
+          {
+            "type": "section",
+            "text": {
+              "type": "plain_text",
+              "text": "Heads up"
+            }
+          },
+          {
+            "type": "context",
+            "elements": [
+              {
+                "type": "image",
+                "image_url": "https://ci.example.com/icon.png",
+                "alt_text": "author icon"
+              },
+              {
+                "type": "mrkdwn",
+                "text": "<https://ci.example.com/|ci &lt;bot&gt;>"
+              }
+            ]
+          },
+          {
+            "type": "section",
+            "text": {
+              "type": "mrkdwn",
+              "text": "*<https://ci.example.com/42|Deploy #42>*"
+            },
+            "accessory": {
+              "type": "image",
+              "image_url": "https://example.com/thumb.png",
+              "alt_text": "thumbnail"
+            }
+          },
+          {
+            "type": "section",
+            "text": {
+              "type": "mrkdwn",
+              "text": "Rolled out *v1.2.3*"
+            }
+          },
+          {
+            "type": "section",
+            "fields": [
+              {
+                "type": "mrkdwn",
+                "text": "*env*\nprod"
+              },
+              {
+                "type": "mrkdwn",
+                "text": "*region*\nus-east-1"
+              }
+            ]
+          },
+          {
+            "type": "actions",
+            "elements": [
+              {
+                "type": "button",
+                "text": {
+                  "type": "plain_text",
+                  "text": "Roll back"
+                },
+                "action_id": "rollback",
+                "value": "42",
+                "style": "danger"
+              }
+            ]
+          },
+          {
+            "type": "context",
+            "elements": [
+              {
+                "type": "mrkdwn",
+                "text": "ci | <!date^1714564800^{date_short_pretty} at {time}|1714564800>"
+              }
+            ]
+          }
+        ]
+        "###);
+    }
+
+    #[test]
+    fn losses() {
+        let attachment = AttachmentBuilder::new("something only the notification says")
+            .color(SlackColor::Warning)
+            .text("body")
+            .fields(vec![Field::new("notes", "a long field", None)])
+            .actions(vec![Action::new("select", "Pick", "picker", None, None)])
+            .callback_id("legacy")
+            .build()
+            .unwrap();
+
+        assert_debug_snapshot!(attachment.to_blocks().losses, @r###"
+        [
+            WideFields,
+            Action(
+                "picker",
+            ),
+            Color(
+                HexColor(
+                    Slack(
+                        Warning,
+                    ),
+                ),
+            ),
+            Fallback(
+                SlackText(
+                    "something only the notification says",
+                ),
+            ),
+            CallbackId(
+                SlackText(
+                    "legacy",
+                ),
+            ),
+        ]
+        "###);
+    }
+
+    #[test]
+    fn thumbnail_without_content() {
+        let attachment = Attachment {
+            thumb_url: Some("https://example.com/thumb.png".parse().unwrap()),
+            ..Default::default()
+        };
+        assert_json_snapshot!(attachment.to_blocks().blocks, @r###"
+        [
+          {
+            "type": "image",
+            "image_url": "https://example.com/thumb.png",
+            "alt_text": "thumbnail"
+          }
+        ]
+        "###);
+    }
+}
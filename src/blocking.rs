@@ -1,36 +1,325 @@
-use crate::{Error, Payload, Result};
-
-use reqwest::{blocking::Client, Url};
+#[cfg(feature = "config")]
+use crate::config::{Config, Profile};
+use crate::dialect::outgoing;
+use crate::{Dialect, Error, IntoWebhookUrl, Payload, Result, WebhookUrl};
 
 /// Handles sending messages to slack
 #[derive(Debug, Clone)]
 pub struct Slack {
-    hook: Url,
-    client: Client,
+    hook: WebhookUrl,
+    backend: Backend,
+    defaults: Option<Payload>,
+}
+
+/// The HTTP client payloads are sent through
+///
+/// Both can be built at once, so enabling one feature never takes the other's constructor away.
+#[derive(Debug, Clone)]
+enum Backend {
+    #[cfg(feature = "blocking")]
+    Reqwest(reqwest::blocking::Client),
+    #[cfg(feature = "ureq")]
+    Ureq(ureq::Agent),
 }
 
 impl Slack {
     /// Construct a new instance of slack for a specific incoming url endpoint.
-    pub fn new<T: reqwest::IntoUrl>(hook: T) -> Result<Slack> {
-        Self::new_with_client(hook, Client::new())
+    ///
+    /// The url is validated as a [`WebhookUrl`], so it must be a `hooks.slack.com` or
+    /// `hooks.slack-gov.com` webhook. Urls for other chat systems are parsed with
+    /// [`WebhookUrl::parse_dialect()`].
+    ///
+    /// Payloads are sent through `reqwest` when the `blocking` feature is enabled, and through
+    /// `ureq` otherwise.
+    pub fn new<T: IntoWebhookUrl>(hook: T) -> Result<Slack> {
+        Self::with_backend(hook, default_backend())
     }
 
-    /// The same as [`Slack::new()`], but with a custom [`reqwest::Client`]
+    /// The same as [`Slack::new()`], but with a custom `reqwest` client
     ///
     /// This allows for configuring custom proxies, DNS resolvers, etc.
-    pub fn new_with_client<T: reqwest::IntoUrl>(hook: T, client: Client) -> Result<Self> {
-        let hook = hook.into_url()?;
-        Ok(Self { hook, client })
+    #[cfg(feature = "blocking")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "blocking")))]
+    pub fn new_with_client<T: IntoWebhookUrl>(
+        hook: T,
+        client: reqwest::blocking::Client,
+    ) -> Result<Self> {
+        Self::with_backend(hook, Backend::Reqwest(client))
+    }
+
+    /// The same as [`Slack::new()`], but sending through a custom `ureq` agent
+    ///
+    /// This allows for configuring custom proxies, timeouts, etc. Build the agent with
+    /// `http_status_as_error(false)` to keep the `Retry-After` of rate limited sends.
+    #[cfg(feature = "ureq")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ureq")))]
+    pub fn new_with_agent<T: IntoWebhookUrl>(hook: T, agent: ureq::Agent) -> Result<Self> {
+        Self::with_backend(hook, Backend::Ureq(agent))
+    }
+
+    fn with_backend<T: IntoWebhookUrl>(hook: T, backend: Backend) -> Result<Self> {
+        let hook = hook.into_webhook_url()?;
+        Ok(Self {
+            hook,
+            backend,
+            defaults: None,
+        })
+    }
+
+    /// Set a template [`Payload`] that is merged into every payload sent
+    ///
+    /// This is handy for giving every message the same `channel`, `username` and icon. Fields set
+    /// on the sent payload win, see [`Payload::with_defaults()`] for the details.
+    pub fn with_defaults(mut self, defaults: Payload) -> Self {
+        self.defaults = Some(defaults);
+        self
+    }
+
+    /// The template [`Payload`] merged into every payload sent, if any
+    pub fn defaults(&self) -> Option<&Payload> {
+        self.defaults.as_ref()
+    }
+
+    /// The chat system that payloads are adapted for, taken from the webhook url
+    pub fn dialect(&self) -> Dialect {
+        self.hook.dialect()
+    }
+
+    /// Construct a new instance of slack from the webhook url held in the environment variable
+    /// `var`, e.g. `SLACK_WEBHOOK_URL`
+    pub fn from_env(var: &str) -> Result<Self> {
+        Self::new(WebhookUrl::from_env(var)?)
+    }
+
+    /// Construct a new instance of slack from the named profile in the default [`Config`]
+    ///
+    /// The profile's default fields are applied to every payload sent.
+    #[cfg(feature = "config")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "config")))]
+    pub fn profile(name: &str) -> Result<Self> {
+        Self::from_profile(Config::load()?.profile(name)?)
+    }
+
+    /// Construct a new instance of slack from a [`Profile`]
+    #[cfg(feature = "config")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "config")))]
+    pub fn from_profile(profile: &Profile) -> Result<Self> {
+        Ok(Self::new(profile.webhook_url()?)?.with_defaults(profile.defaults()))
     }
 
     /// Send payload to slack service
+    ///
+    /// The payload is merged with the [`Slack::defaults()`] first, if any are set, and then adapted
+    /// to the [`Slack::dialect()`].
     pub fn send(&self, payload: &Payload) -> Result<()> {
-        let response = self.client.post(self.hook.clone()).json(payload).send()?;
+        self.post(&outgoing(payload, self.defaults.as_ref(), self.dialect()))
+    }
+
+    fn post(&self, payload: &Payload) -> Result<()> {
+        match &self.backend {
+            #[cfg(feature = "blocking")]
+            Backend::Reqwest(client) => post_reqwest(client, &self.hook, payload),
+            #[cfg(feature = "ureq")]
+            Backend::Ureq(agent) => post_ureq(agent, &self.hook, payload),
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+fn post_reqwest(
+    client: &reqwest::blocking::Client,
+    hook: &WebhookUrl,
+    payload: &Payload,
+) -> Result<()> {
+    use reqwest::{header::RETRY_AFTER, StatusCode};
+
+    let response = client
+        .post(hook.as_url().clone())
+        .json(payload)
+        .send()
+        // the url holds the webhook's secret
+        .map_err(|err| Error::from(err.without_url()))?;
+
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response.headers().get(RETRY_AFTER);
+        Err(Error::rate_limited(
+            retry_after.and_then(|value| value.to_str().ok()),
+        ))
+    } else if status.is_success() {
+        Ok(())
+    } else {
+        let body = response.text().unwrap_or_default();
+        Err(Error::http_status(status, status.is_client_error(), &body))
+    }
+}
+
+#[cfg(feature = "ureq")]
+fn post_ureq(agent: &ureq::Agent, hook: &WebhookUrl, payload: &Payload) -> Result<()> {
+    use ureq::http::{header::RETRY_AFTER, StatusCode};
+
+    let mut response = match agent.post(hook.as_url().as_str()).send_json(payload) {
+        Ok(response) => response,
+        Err(ureq::Error::StatusCode(429)) => return Err(Error::RateLimited(None)),
+        // only returned when the agent was configured to treat error statuses as errors
+        Err(ureq::Error::StatusCode(code)) => {
+            let status = StatusCode::from_u16(code).map_err(|err| Error::Slack(err.to_string()))?;
+            return Err(Error::http_status(status, status.is_client_error(), ""));
+        }
+        Err(err) => return Err(without_url(err, hook)),
+    };
+
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response.headers().get(RETRY_AFTER);
+        Err(Error::rate_limited(
+            retry_after.and_then(|value| value.to_str().ok()),
+        ))
+    } else if status.is_success() {
+        Ok(())
+    } else {
+        let body = response.body_mut().read_to_string().unwrap_or_default();
+        Err(Error::http_status(status, status.is_client_error(), &body))
+    }
+}
+
+/// A few of ureq's errors quote the url, which holds the webhook's secret
+#[cfg(feature = "ureq")]
+fn without_url(err: ureq::Error, hook: &WebhookUrl) -> Error {
+    match err {
+        ureq::Error::BadUri(_) | ureq::Error::RequireHttpsOnly(_) => {
+            Error::WebhookUrl(format!("rejected by the HTTP client: `{hook}`"))
+        }
+        err => err.into(),
+    }
+}
+
+#[cfg(feature = "blocking")]
+fn default_backend() -> Backend {
+    Backend::Reqwest(reqwest::blocking::Client::new())
+}
+
+#[cfg(not(feature = "blocking"))]
+fn default_backend() -> Backend {
+    let agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .build()
+        .into();
+    Backend::Ureq(agent)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Slack;
+    use crate::test_util::{hook_url, mock_hook, refused_url};
+    use crate::{Payload, PayloadBuilder, Result, WebhookUrl};
+    use insta::{assert_json_snapshot, assert_snapshot};
+    use serde_json::Value;
+    use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
 
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            Err(Error::Slack(format!("HTTP error {}", response.status())))
+    async fn send(hook: WebhookUrl, defaults: Option<Payload>) -> Result<()> {
+        send_with(Slack::new, hook, defaults).await
+    }
+
+    // the blocking client has to be created and dropped outside of the async runtime
+    async fn send_with(
+        new: fn(WebhookUrl) -> Result<Slack>,
+        hook: WebhookUrl,
+        defaults: Option<Payload>,
+    ) -> Result<()> {
+        tokio::task::spawn_blocking(move || {
+            let mut slack = new(hook).unwrap();
+            if let Some(defaults) = defaults {
+                slack = slack.with_defaults(defaults);
+            }
+            let payload = PayloadBuilder::new().text("hello").build().unwrap();
+            slack.send(&payload)
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sends_payload() {
+        let (server, hook) = mock_hook(200).await;
+        let defaults = PayloadBuilder::new().channel("#alerts").build().unwrap();
+        send(hook, Some(defaults)).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_json_snapshot!(body, @r###"
+        {
+          "channel": "#alerts",
+          "text": "hello"
         }
+        "###);
+    }
+
+    #[tokio::test]
+    async fn error_status() {
+        let (_server, hook) = mock_hook(500).await;
+        let err = send(hook, None).await.unwrap_err();
+        assert_snapshot!(err, @"slack service error: HTTP error 500 Internal Server Error");
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("invalid_payload"))
+            .mount(&server)
+            .await;
+        let err = send(hook_url(&server), None).await.unwrap_err();
+        assert_snapshot!(err, @"slack rejected the payload: HTTP error 400 Bad Request: invalid_payload");
+    }
+
+    #[tokio::test]
+    async fn rate_limited() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "30"))
+            .mount(&server)
+            .await;
+        let err = send(hook_url(&server), None).await.unwrap_err();
+        assert_snapshot!(err, @"slack rate limit hit, retry after 30s");
+
+        let (_server, hook) = mock_hook(429).await;
+        let err = send(hook, None).await.unwrap_err();
+        assert_snapshot!(err, @"slack rate limit hit");
+    }
+
+    #[cfg(feature = "ureq")]
+    #[tokio::test]
+    async fn custom_agent() {
+        let new = |hook| {
+            let agent = ureq::Agent::config_builder()
+                .http_status_as_error(false)
+                .build()
+                .into();
+            Slack::new_with_agent(hook, agent)
+        };
+        let (server, hook) = mock_hook(200).await;
+        send_with(new, hook, None).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        let (_server, hook) = mock_hook(500).await;
+        let err = send_with(new, hook, None).await.unwrap_err();
+        assert_snapshot!(err, @"slack service error: HTTP error 500 Internal Server Error");
+
+        // with ureq's default config error statuses come back as errors, without their headers
+        let (_server, hook) = mock_hook(429).await;
+        let err = send_with(
+            |hook| Slack::new_with_agent(hook, ureq::Agent::new_with_defaults()),
+            hook,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert_snapshot!(err, @"slack rate limit hit");
+    }
+
+    #[tokio::test]
+    async fn transport_error_hides_secret() {
+        let hook = WebhookUrl::new_unchecked(refused_url("/services/T1/B2/s3cr3t"));
+        let err = send(hook, None).await.unwrap_err();
+        let err = format!("{err} {err:?}");
+        assert!(!err.contains("s3cr3t"), "{}", err);
     }
 }
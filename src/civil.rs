@@ -0,0 +1,40 @@
+//! Calendar arithmetic for the proleptic Gregorian calendar, in UTC
+//!
+//! From <https://howardhinnant.github.io/date_algorithms.html>
+
+/// Days since the unix epoch of a date
+#[cfg(feature = "alertmanager")]
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The `(year, month, day)` of a day since the unix epoch, the inverse of [`days_from_civil()`]
+#[cfg(any(feature = "alertmanager", feature = "scheduler"))]
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Day of the week of a day since the unix epoch, with Sunday as `0`
+#[cfg(feature = "scheduler")]
+pub(crate) fn weekday_from_days(days: i64) -> i64 {
+    (days + 4).rem_euclid(7)
+}
@@ -0,0 +1,212 @@
+#[cfg(feature = "config")]
+use crate::config::{Config, Profile};
+use crate::dialect::outgoing;
+use crate::{Dialect, Error, IntoWebhookUrl, Payload, Result, WebhookUrl};
+use reqwest::{header::RETRY_AFTER, Client, StatusCode};
+
+/// Handles sending messages to slack
+#[derive(Debug, Clone)]
+pub struct Slack {
+    hook: WebhookUrl,
+    client: Client,
+    defaults: Option<Payload>,
+}
+
+impl Slack {
+    /// Construct a new instance of slack for a specific incoming url endpoint.
+    ///
+    /// The url is validated as a [`WebhookUrl`], so it must be a `hooks.slack.com` or
+    /// `hooks.slack-gov.com` webhook. Urls for other chat systems are parsed with
+    /// [`WebhookUrl::parse_dialect()`].
+    pub fn new<T: IntoWebhookUrl>(hook: T) -> Result<Slack> {
+        Self::new_with_client(hook, Client::new())
+    }
+
+    /// The same as [`Slack::new()`], but with a custom [`reqwest::Client`]
+    ///
+    /// This allows for configuring custom proxies, DNS resolvers, etc.
+    pub fn new_with_client<T: IntoWebhookUrl>(hook: T, client: Client) -> Result<Self> {
+        let hook = hook.into_webhook_url()?;
+        Ok(Self {
+            hook,
+            client,
+            defaults: None,
+        })
+    }
+
+    /// Set a template [`Payload`] that is merged into every payload sent
+    ///
+    /// This is handy for giving every message the same `channel`, `username` and icon. Fields set
+    /// on the sent payload win, see [`Payload::with_defaults()`] for the details.
+    pub fn with_defaults(mut self, defaults: Payload) -> Self {
+        self.defaults = Some(defaults);
+        self
+    }
+
+    /// The template [`Payload`] merged into every payload sent, if any
+    pub fn defaults(&self) -> Option<&Payload> {
+        self.defaults.as_ref()
+    }
+
+    /// The chat system that payloads are adapted for, taken from the webhook url
+    pub fn dialect(&self) -> Dialect {
+        self.hook.dialect()
+    }
+
+    /// Construct a new instance of slack from the webhook url held in the environment variable
+    /// `var`, e.g. `SLACK_WEBHOOK_URL`
+    pub fn from_env(var: &str) -> Result<Self> {
+        Self::new(WebhookUrl::from_env(var)?)
+    }
+
+    /// Construct a new instance of slack from the named profile in the default [`Config`]
+    ///
+    /// The profile's default fields are applied to every payload sent.
+    #[cfg(feature = "config")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "config")))]
+    pub fn profile(name: &str) -> Result<Self> {
+        Self::from_profile(Config::load()?.profile(name)?)
+    }
+
+    /// Construct a new instance of slack from a [`Profile`]
+    #[cfg(feature = "config")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "config")))]
+    pub fn from_profile(profile: &Profile) -> Result<Self> {
+        Ok(Self::new(profile.webhook_url()?)?.with_defaults(profile.defaults()))
+    }
+
+    /// Send payload to slack service
+    ///
+    /// The payload is merged with the [`Slack::defaults()`] first, if any are set, and then adapted
+    /// to the [`Slack::dialect()`].
+    pub async fn send(&self, payload: &Payload) -> Result<()> {
+        let payload = outgoing(payload, self.defaults.as_ref(), self.dialect());
+        let response = self
+            .client
+            .post(self.hook.as_url().clone())
+            .json(&payload)
+            .send()
+            .await
+            // the url holds the webhook's secret
+            .map_err(|err| Error::from(err.without_url()))?;
+
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response.headers().get(RETRY_AFTER);
+            Err(Error::rate_limited(
+                retry_after.and_then(|value| value.to_str().ok()),
+            ))
+        } else if status.is_success() {
+            Ok(())
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            Err(Error::http_status(status, status.is_client_error(), &body))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Slack;
+    use crate::test_util::{hook_url, mock_hook, refused_url};
+    use crate::{Error, PayloadBuilder, WebhookUrl};
+    use insta::{assert_json_snapshot, assert_snapshot};
+    use serde_json::Value;
+    use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn sends_payload() {
+        let (server, hook) = mock_hook(200).await;
+        let slack = Slack::new(hook).unwrap();
+        let payload = PayloadBuilder::new().text("hello").build().unwrap();
+        slack.send(&payload).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_json_snapshot!(body, @r###"
+        {
+          "text": "hello"
+        }
+        "###);
+    }
+
+    #[tokio::test]
+    async fn error_status() {
+        let (_server, hook) = mock_hook(503).await;
+        let slack = Slack::new(hook).unwrap();
+        let payload = PayloadBuilder::new().text("hello").build().unwrap();
+        let err = slack.send(&payload).await.unwrap_err();
+        assert_snapshot!(err, @"slack service error: HTTP error 503 Service Unavailable");
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("no_service"))
+            .mount(&server)
+            .await;
+        let slack = Slack::new(hook_url(&server)).unwrap();
+        let err = slack.send(&payload).await.unwrap_err();
+        assert!(matches!(err, Error::Rejected(_)), "{:?}", err);
+        assert_snapshot!(err, @"slack rejected the payload: HTTP error 404 Not Found: no_service");
+    }
+
+    #[tokio::test]
+    async fn rate_limited() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "30"))
+            .mount(&server)
+            .await;
+        let slack = Slack::new(hook_url(&server)).unwrap();
+        let payload = PayloadBuilder::new().text("hello").build().unwrap();
+        let err = slack.send(&payload).await.unwrap_err();
+        assert_snapshot!(err, @"slack rate limit hit, retry after 30s");
+    }
+
+    #[tokio::test]
+    async fn transport_error_hides_secret() {
+        let hook = WebhookUrl::new_unchecked(refused_url("/services/T1/B2/s3cr3t"));
+        let payload = PayloadBuilder::new().text("hello").build().unwrap();
+        let err = Slack::new(hook).unwrap().send(&payload).await.unwrap_err();
+        let err = format!("{err} {err:?}");
+        assert!(!err.contains("s3cr3t"), "{}", err);
+    }
+
+    #[test]
+    fn slack_incoming_url() {
+        let s = Slack::new("https://hooks.slack.com/services/abc/123/45z").unwrap();
+        assert_snapshot!(s.hook.as_url(), @"https://hooks.slack.com/services/abc/123/45z");
+    }
+
+    #[test]
+    fn slack_debug_redacts_secret() {
+        let s = Slack::new("https://hooks.slack.com/services/abc/123/45z").unwrap();
+        assert!(!format!("{s:?}").contains("45z"));
+    }
+
+    #[test]
+    fn slack_from_env() {
+        std::env::set_var(
+            "SLACK_HOOK_TEST_FROM_ENV",
+            "https://hooks.slack.com/services/abc/123/45z",
+        );
+        let s = Slack::from_env("SLACK_HOOK_TEST_FROM_ENV").unwrap();
+        assert_eq!(s.hook.team(), "abc");
+
+        let err = Slack::from_env("SLACK_HOOK_TEST_UNSET").unwrap_err();
+        assert_snapshot!(err, @"configuration error: `SLACK_HOOK_TEST_UNSET`: environment variable not found");
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn slack_from_profile() {
+        let config: crate::config::Config = r##"
+            [profiles.alerts]
+            url = "https://hooks.slack.com/services/abc/123/45z"
+            channel = "#alerts"
+        "##
+        .parse()
+        .unwrap();
+        let s = Slack::from_profile(config.profile("alerts").unwrap()).unwrap();
+        assert_eq!(s.defaults().unwrap().channel.as_deref(), Some("#alerts"));
+    }
+}
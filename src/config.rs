@@ -0,0 +1,272 @@
+//! Named webhook profiles loaded from a TOML file
+//!
+//! ```toml
+//! [profiles.alerts]
+//! url = "https://hooks.slack.com/services/T000/B000/XXXX"
+//! channel = "#alerts"
+//! username = "Alert Bot"
+//! icon_emoji = ":rotating_light:"
+//!
+//! [profiles.deploys]
+//! # a file holding only the url, e.g. a mounted secret. Relative to the config file
+//! url_file = "secrets/deploys-webhook"
+//!
+//! [profiles.team-chat]
+//! url = "http://chat.internal/hooks/xa8pw3ukqfbwjbhfp5jsnwjx1a"
+//! dialect = "mattermost"
+//! ```
+//!
+//! [`Config::load()`] reads the file named by `SLACK_HOOK_CONFIG`, falling back to
+//! `$XDG_CONFIG_HOME/slack-hook/config.toml` and then `~/.config/slack-hook/config.toml`.
+
+use crate::{Dialect, Error, Payload, Result, WebhookUrl};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use url::Url;
+
+/// Environment variable holding the path of the config file
+pub const CONFIG_ENV_VAR: &str = "SLACK_HOOK_CONFIG";
+
+/// A set of named webhook profiles
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Profiles keyed by their name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Config {
+    /// Load the config from its default location
+    pub fn load() -> Result<Config> {
+        Config::from_path(default_path()?)
+    }
+
+    /// Load the config from a specific file
+    ///
+    /// Relative `url_file` paths are resolved against the directory holding the config file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Config> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|err| Error::Config(format!("failed reading `{}`: {err}", path.display())))?;
+        let mut config: Config = contents.parse()?;
+
+        if let Some(dir) = path.parent() {
+            for profile in config.profiles.values_mut() {
+                if let Some(url_file) = &mut profile.url_file {
+                    *url_file = dir.join(&*url_file);
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Look up a profile by name
+    pub fn profile(&self, name: &str) -> Result<&Profile> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| Error::Config(format!("no profile named `{name}`")))
+    }
+}
+
+impl FromStr for Config {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+/// A webhook along with the default fields applied to each message sent through it
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(try_from = "ProfileDef")]
+pub struct Profile {
+    /// The webhook url
+    pub url: Option<WebhookUrl>,
+    /// A file holding the webhook url, as an alternative to `url`
+    pub url_file: Option<PathBuf>,
+    /// The chat system the webhook belongs to, slack by default
+    pub dialect: Dialect,
+    /// Default channel
+    pub channel: Option<String>,
+    /// Default username
+    pub username: Option<String>,
+    /// Default icon emoji
+    pub icon_emoji: Option<String>,
+    /// Default icon url
+    pub icon_url: Option<Url>,
+}
+
+impl Profile {
+    /// Get the webhook url, reading it from `url_file` if needed
+    ///
+    /// Exactly one of `url` and `url_file` must be set.
+    pub fn webhook_url(&self) -> Result<WebhookUrl> {
+        match (&self.url, &self.url_file) {
+            (Some(url), None) => Ok(url.clone()),
+            (None, Some(path)) => {
+                let url = fs::read_to_string(path).map_err(|err| {
+                    Error::Config(format!("failed reading `{}`: {err}", path.display()))
+                })?;
+                WebhookUrl::parse_dialect(url.trim(), self.dialect)
+            }
+            (Some(_), Some(_)) => Err(Error::Config(
+                "only one of `url` and `url_file` can be set".to_owned(),
+            )),
+            (None, None) => Err(Error::Config(
+                "one of `url` or `url_file` must be set".to_owned(),
+            )),
+        }
+    }
+
+    /// The payload fields this profile applies to every message
+    pub fn defaults(&self) -> Payload {
+        Payload {
+            channel: self.channel.clone(),
+            username: self.username.clone(),
+            icon_emoji: self.icon_emoji.clone(),
+            icon_url: self.icon_url.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A [`Profile`] as written in the config file, where `url` is parsed for the `dialect`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileDef {
+    url: Option<String>,
+    url_file: Option<PathBuf>,
+    #[serde(default)]
+    dialect: Dialect,
+    channel: Option<String>,
+    username: Option<String>,
+    icon_emoji: Option<String>,
+    icon_url: Option<Url>,
+}
+
+impl TryFrom<ProfileDef> for Profile {
+    type Error = Error;
+
+    fn try_from(def: ProfileDef) -> Result<Self> {
+        let dialect = def.dialect;
+        Ok(Profile {
+            url: def
+                .url
+                .map(|url| WebhookUrl::parse_dialect(&url, dialect))
+                .transpose()?,
+            url_file: def.url_file,
+            dialect: def.dialect,
+            channel: def.channel,
+            username: def.username,
+            icon_emoji: def.icon_emoji,
+            icon_url: def.icon_url,
+        })
+    }
+}
+
+fn default_path() -> Result<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_ENV_VAR) {
+        return Ok(path.into());
+    }
+
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .ok_or_else(|| {
+            Error::Config(format!(
+                "couldn't find a config directory, try setting `{CONFIG_ENV_VAR}`"
+            ))
+        })?;
+    Ok(config_dir.join("slack-hook").join("config.toml"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    const CONFIG: &str = r##"
+        [profiles.alerts]
+        url = "https://hooks.slack.com/services/T1/B2/s3cr3t"
+        channel = "#alerts"
+        username = "Alert Bot"
+        icon_emoji = ":rotating_light:"
+
+        [profiles.from-file]
+        url_file = "webhook"
+
+        [profiles.team-chat]
+        url = "http://chat.internal/hooks/key"
+        dialect = "mattermost"
+    "##;
+
+    #[test]
+    fn profile_defaults() {
+        let config: Config = CONFIG.parse().unwrap();
+        let alerts = config.profile("alerts").unwrap();
+        assert_eq!(alerts.webhook_url().unwrap().team(), "T1");
+        insta::assert_json_snapshot!(alerts.defaults(), @r###"
+        {
+          "channel": "#alerts",
+          "username": "Alert Bot",
+          "icon_emoji": ":rotating_light:"
+        }
+        "###);
+    }
+
+    #[test]
+    fn url_file_is_relative_to_config() {
+        let dir = env::temp_dir().join(format!("slack-hook-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("config.toml"), CONFIG).unwrap();
+        fs::write(
+            dir.join("webhook"),
+            "https://hooks.slack.com/services/T3/B4/s3cr3t\n",
+        )
+        .unwrap();
+
+        let config = Config::from_path(dir.join("config.toml")).unwrap();
+        let url = config.profile("from-file").unwrap().webhook_url().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(url.bot(), "B4");
+    }
+
+    #[test]
+    fn profile_dialect() {
+        let config: Config = CONFIG.parse().unwrap();
+        let url = config.profile("team-chat").unwrap().webhook_url().unwrap();
+        assert_eq!(url.dialect(), Dialect::Mattermost);
+
+        let err = "[profiles.chat]\nurl = \"http://chat.internal/hooks/key\""
+            .parse::<Config>()
+            .unwrap_err();
+        assert_snapshot!(err, @r###"
+        TOML parse error at line 1, column 1
+          |
+        1 | [profiles.chat]
+          | ^^^^^^^^^^^^^^^
+        invalid webhook url: Must use https: found `http`
+        "###);
+    }
+
+    #[test]
+    fn missing_profile() {
+        let config: Config = CONFIG.parse().unwrap();
+        let err = config.profile("nope").unwrap_err();
+        assert_snapshot!(err, @"configuration error: no profile named `nope`");
+    }
+
+    #[test]
+    fn missing_url() {
+        let config: Config = "[profiles.empty]".parse().unwrap();
+        let err = config.profile("empty").unwrap().webhook_url().unwrap_err();
+        assert_snapshot!(err, @"configuration error: one of `url` or `url_file` must be set");
+    }
+}
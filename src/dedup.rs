@@ -0,0 +1,555 @@
+//! Suppress repeats of the same message, e.g. from a flapping check
+//!
+//! [`Dedup`] wraps a [`Slack`] client. The first payload with a given [`Fingerprint`] is sent
+//! right away and opens a window. Repeats within the window are suppressed, and when it closes a
+//! summary like "Repeated 4 times in the last 10m" is sent in their place.
+//!
+//! ```rust,no_run
+//! # async fn run() -> slack_hook::Result<()> {
+//! use slack_hook::dedup::{Dedup, Fingerprint};
+//! use slack_hook::{PayloadBuilder, Slack};
+//! use std::time::Duration;
+//!
+//! let dedup = Dedup::new(Slack::from_env("SLACK_WEBHOOK_URL")?, Duration::from_secs(10 * 60))
+//!     .fingerprint(Fingerprint::AttachmentTitles);
+//! let payload = PayloadBuilder::new().text("db-1 is down").build()?;
+//! dedup.send(&payload).await?;
+//! // suppressed, and counted in the summary
+//! dedup.send(&payload).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::background::{ignore_errors, ErrorHandler};
+use crate::{Error, Payload, Result, Slack, SlackText};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::time::Instant;
+
+type KeyFn = Arc<dyn Fn(&Payload) -> String + Send + Sync>;
+
+/// Which parts of a payload make it a repeat of another
+///
+/// The payload's `channel` is always part of the fingerprint, so the same message sent to two
+/// channels isn't a repeat.
+#[derive(Clone, Default)]
+pub enum Fingerprint {
+    /// The `text`, along with the attachments' titles
+    #[default]
+    TextAndAttachmentTitles,
+    /// Only the `text`
+    Text,
+    /// Only the attachments' titles, or their fallbacks when untitled
+    AttachmentTitles,
+    /// A key made by the caller, e.g. from an alert's name and instance
+    Key(KeyFn),
+}
+
+impl Fingerprint {
+    /// Fingerprint payloads with a key made by `key`
+    pub fn key<F>(key: F) -> Fingerprint
+    where
+        F: Fn(&Payload) -> String + Send + Sync + 'static,
+    {
+        Fingerprint::Key(Arc::new(key))
+    }
+
+    /// The fingerprint of a payload
+    pub fn of(&self, payload: &Payload) -> String {
+        let text = || payload.text.as_ref().map(SlackText::to_string);
+        let titles = || {
+            payload
+                .attachments
+                .iter()
+                .flatten()
+                .map(|attachment| attachment.title.as_ref().unwrap_or(&attachment.fallback))
+                .map(SlackText::to_string)
+        };
+        let parts: Vec<String> = match self {
+            Fingerprint::TextAndAttachmentTitles => text().into_iter().chain(titles()).collect(),
+            Fingerprint::Text => text().into_iter().collect(),
+            Fingerprint::AttachmentTitles => titles().collect(),
+            Fingerprint::Key(key) => vec![key(payload)],
+        };
+        key_of(payload.channel.as_deref(), &parts)
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fingerprint::TextAndAttachmentTitles => f.write_str("TextAndAttachmentTitles"),
+            Fingerprint::Text => f.write_str("Text"),
+            Fingerprint::AttachmentTitles => f.write_str("AttachmentTitles"),
+            Fingerprint::Key(_) => f.write_str("Key(..)"),
+        }
+    }
+}
+
+/// Hash the parts with FNV-1a, which unlike `std`'s hasher is stable enough to keep in a shared
+/// [`DedupStore`]
+fn key_of(channel: Option<&str>, parts: &[String]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let channel = channel.unwrap_or_default();
+    for part in std::iter::once(channel).chain(parts.iter().map(String::as_str)) {
+        // a separator that can't appear in text, so `["ab", "c"]` and `["a", "bc"]` differ
+        for byte in part.bytes().chain(std::iter::once(0xff)) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+/// The repeats suppressed during a window
+#[derive(Debug, Clone)]
+pub struct Suppressed {
+    /// How many payloads were suppressed
+    pub count: u32,
+    /// The most recent of them, which the summary is based on
+    pub latest: Payload,
+}
+
+/// What [`DedupStore::record()`] made of a payload
+#[derive(Debug, Clone)]
+pub enum Recorded {
+    /// It opened a new window, and should be sent
+    Opened {
+        /// Identifies the window when it is closed, so closing a window that has since been
+        /// replaced leaves the new one open
+        id: u64,
+        /// What an earlier window for the same fingerprint suppressed, if that window had run out
+        /// but not been closed yet, so its summary can still be sent
+        expired: Option<Box<Suppressed>>,
+    },
+    /// It repeated a payload within an open window, and was counted
+    Suppressed,
+}
+
+/// Where [`Dedup`] keeps its open windows
+///
+/// [`MemoryStore`] is used by default. Implement this to share windows between processes, e.g.
+/// in Redis.
+///
+/// The methods are called straight from [`Dedup::send()`]'s async code, so they should return
+/// quickly. A store that waits on the network blocks the runtime's thread meanwhile, so wrap those
+/// calls in `tokio::task::block_in_place()` on a multi-threaded runtime.
+pub trait DedupStore: Send + Sync {
+    /// Record a payload with `fingerprint`, either opening a new window or counting a repeat
+    ///
+    /// A repeat is kept as the window's latest payload. Windows older than `window` are treated
+    /// as closed, but whatever they suppressed must be returned with the new window rather than
+    /// dropped.
+    fn record(&self, fingerprint: &str, payload: &Payload, window: Duration) -> Recorded;
+
+    /// Close the window for `fingerprint`, returning what it suppressed, if anything
+    ///
+    /// Does nothing unless the open window is the one [`DedupStore::record()`] returned `id` for.
+    fn close(&self, fingerprint: &str, id: u64) -> Option<Suppressed>;
+}
+
+/// Keeps windows in memory, the default [`DedupStore`]
+///
+/// Windows are timed with Tokio's clock, the same one [`Dedup`] closes them by.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    windows: Mutex<HashMap<String, Window>>,
+    next_id: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Window {
+    id: u64,
+    opened: Instant,
+    suppressed: Option<Suppressed>,
+}
+
+impl MemoryStore {
+    /// Construct an empty store
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    fn windows(&self) -> std::sync::MutexGuard<'_, HashMap<String, Window>> {
+        self.windows
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl DedupStore for MemoryStore {
+    fn record(&self, fingerprint: &str, payload: &Payload, window: Duration) -> Recorded {
+        let mut windows = self.windows();
+        match windows.get_mut(fingerprint) {
+            Some(open) if open.opened.elapsed() < window => {
+                let count = open.suppressed.as_ref().map_or(0, |s| s.count);
+                open.suppressed = Some(Suppressed {
+                    count: count + 1,
+                    latest: payload.clone(),
+                });
+                Recorded::Suppressed
+            }
+            _ => {
+                let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                let expired = windows.insert(
+                    fingerprint.to_owned(),
+                    Window {
+                        id,
+                        opened: Instant::now(),
+                        suppressed: None,
+                    },
+                );
+                Recorded::Opened {
+                    id,
+                    expired: expired.and_then(|window| window.suppressed).map(Box::new),
+                }
+            }
+        }
+    }
+
+    fn close(&self, fingerprint: &str, id: u64) -> Option<Suppressed> {
+        let mut windows = self.windows();
+        match windows.get(fingerprint) {
+            Some(open) if open.id == id => windows.remove(fingerprint)?.suppressed,
+            _ => None,
+        }
+    }
+}
+
+/// Whether [`Dedup::send()`] sent a payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// It was sent, opening a window
+    Sent,
+    /// It repeated a payload sent within the window, and will be counted in its summary
+    Suppressed,
+}
+
+/// Sends payloads through [`Slack::send()`], suppressing repeats within a window
+///
+/// [`Dedup::send()`] spawns a task to send each window's summary when it closes, so it panics
+/// outside a Tokio runtime.
+#[derive(Clone)]
+pub struct Dedup {
+    slack: Arc<Slack>,
+    window: Duration,
+    fingerprint: Fingerprint,
+    store: Arc<dyn DedupStore>,
+    on_error: ErrorHandler,
+}
+
+impl Dedup {
+    /// Construct a dedup layer around `slack`, suppressing repeats for `window`
+    pub fn new(slack: Slack, window: Duration) -> Dedup {
+        Dedup {
+            slack: Arc::new(slack),
+            window,
+            fingerprint: Fingerprint::default(),
+            store: Arc::new(MemoryStore::new()),
+            on_error: ignore_errors(),
+        }
+    }
+
+    /// Set which parts of a payload make it a repeat
+    pub fn fingerprint(mut self, fingerprint: Fingerprint) -> Dedup {
+        self.fingerprint = fingerprint;
+        self
+    }
+
+    /// Keep windows in `store` instead of in memory
+    pub fn store<S: DedupStore + 'static>(mut self, store: S) -> Dedup {
+        self.store = Arc::new(store);
+        self
+    }
+
+    /// Report summaries that fail to send to `on_error`, since nothing else sees them
+    pub fn on_error<F>(mut self, on_error: F) -> Dedup
+    where
+        F: Fn(Error) + Send + Sync + 'static,
+    {
+        self.on_error = Arc::new(on_error);
+        self
+    }
+
+    /// Send a payload, unless it repeats one sent within the window
+    pub async fn send(&self, payload: &Payload) -> Result<Delivery> {
+        let fingerprint = self.fingerprint.of(payload);
+        self.send_fingerprinted(fingerprint, payload).await
+    }
+
+    /// The same as [`Dedup::send()`], but with a key for this payload in place of its fingerprint
+    pub async fn send_with_key(&self, key: &str, payload: &Payload) -> Result<Delivery> {
+        let fingerprint = key_of(payload.channel.as_deref(), &[key.to_owned()]);
+        self.send_fingerprinted(fingerprint, payload).await
+    }
+
+    async fn send_fingerprinted(&self, fingerprint: String, payload: &Payload) -> Result<Delivery> {
+        let opened = Instant::now();
+        let id = match self.store.record(&fingerprint, payload, self.window) {
+            Recorded::Opened { id, expired } => {
+                // the earlier window ran out before its summary went out, so send it first
+                if let Some(suppressed) = expired {
+                    if let Err(err) = self.slack.send(&summary(&suppressed, self.window)).await {
+                        (self.on_error)(err);
+                    }
+                }
+                id
+            }
+            Recorded::Suppressed => return Ok(Delivery::Suppressed),
+        };
+        if let Err(err) = self.slack.send(payload).await {
+            // nothing was delivered, so the next repeat should be sent rather than suppressed
+            let _ = self.store.close(&fingerprint, id);
+            return Err(err);
+        }
+
+        let slack = Arc::clone(&self.slack);
+        let store = Arc::clone(&self.store);
+        let on_error = Arc::clone(&self.on_error);
+        let window = self.window;
+        let _summary = tokio::spawn(async move {
+            tokio::time::sleep_until(opened + window).await;
+            if let Some(suppressed) = store.close(&fingerprint, id) {
+                if let Err(err) = slack.send(&summary(&suppressed, window)).await {
+                    on_error(err);
+                }
+            }
+        });
+        Ok(Delivery::Sent)
+    }
+}
+
+impl fmt::Debug for Dedup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dedup")
+            .field("slack", &self.slack)
+            .field("window", &self.window)
+            .field("fingerprint", &self.fingerprint)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The latest suppressed payload, with its text saying how often it repeated
+fn summary(suppressed: &Suppressed, window: Duration) -> Payload {
+    let times = match suppressed.count {
+        1 => "once".to_owned(),
+        count => format!("{count} times"),
+    };
+    let repeated = format!("Repeated {times} in the last {}", format_window(window));
+    let text = match &suppressed.latest.text {
+        Some(text) => SlackText::new_raw(format!("{text}\n_{repeated}_")),
+        None => SlackText::new(format!("_{repeated}_")),
+    };
+    Payload {
+        text: Some(text),
+        ..suppressed.latest.clone()
+    }
+}
+
+/// Format a window like `10m` or `1h 30m`
+fn format_window(window: Duration) -> String {
+    let secs = window.as_secs();
+    if secs == 0 {
+        return format!("{}ms", window.as_millis());
+    }
+
+    let parts = [(secs / 3600, "h"), (secs / 60 % 60, "m"), (secs % 60, "s")];
+    parts
+        .iter()
+        .filter(|(amount, _)| *amount > 0)
+        .map(|(amount, unit)| format!("{amount}{unit}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{hook_url, received, respond_with};
+    use crate::{AttachmentBuilder, PayloadBuilder};
+    use insta::assert_json_snapshot;
+    use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+    async fn dedup(server: &MockServer, window: Duration) -> Dedup {
+        respond_with(server, 200).await;
+        Dedup::new(Slack::new(hook_url(server)).unwrap(), window)
+    }
+
+    fn alert(title: &str, text: &str) -> Payload {
+        let attachment = AttachmentBuilder::new(title).title(title).build().unwrap();
+        PayloadBuilder::new()
+            .text(text)
+            .attachments(vec![attachment])
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn summarizes_repeats() {
+        let server = MockServer::start().await;
+        let dedup = dedup(&server, Duration::from_millis(200)).await;
+
+        let payload = PayloadBuilder::new().text("db-1 is down").build().unwrap();
+        assert_eq!(dedup.send(&payload).await.unwrap(), Delivery::Sent);
+        for _ in 0..3 {
+            assert_eq!(dedup.send(&payload).await.unwrap(), Delivery::Suppressed);
+        }
+        // the summary is sent once the window has closed, so this opens a new one
+        tokio::time::pause();
+        let _ = received(&server, 2).await;
+        assert_eq!(dedup.send(&payload).await.unwrap(), Delivery::Sent);
+
+        assert_json_snapshot!(received(&server, 3).await, @r###"
+        [
+          {
+            "text": "db-1 is down"
+          },
+          {
+            "text": "db-1 is down\n_Repeated 3 times in the last 200ms_"
+          },
+          {
+            "text": "db-1 is down"
+          }
+        ]
+        "###);
+    }
+
+    #[tokio::test]
+    async fn no_summary_without_repeats() {
+        let server = MockServer::start().await;
+        let dedup = dedup(&server, Duration::from_millis(100)).await;
+
+        let payload = PayloadBuilder::new().text("deployed").build().unwrap();
+        assert_eq!(dedup.send(&payload).await.unwrap(), Delivery::Sent);
+        tokio::time::pause();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_send_closes_window() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        let dedup = dedup(&server, Duration::from_secs(600)).await;
+
+        let payload = PayloadBuilder::new().text("db-1 is down").build().unwrap();
+        let err = dedup.send(&payload).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "slack service error: HTTP error 500 Internal Server Error"
+        );
+        assert_eq!(dedup.send(&payload).await.unwrap(), Delivery::Sent);
+        assert_eq!(dedup.send(&payload).await.unwrap(), Delivery::Suppressed);
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn repeat_as_window_runs_out() {
+        let server = MockServer::start().await;
+        let window = Duration::from_secs(600);
+        let dedup = dedup(&server, window).await;
+
+        tokio::time::pause();
+        let payload = PayloadBuilder::new().text("db-1 is down").build().unwrap();
+        assert_eq!(dedup.send(&payload).await.unwrap(), Delivery::Sent);
+        assert_eq!(dedup.send(&payload).await.unwrap(), Delivery::Suppressed);
+        tokio::time::advance(window).await;
+        assert_eq!(dedup.send(&payload).await.unwrap(), Delivery::Sent);
+        assert_eq!(dedup.send(&payload).await.unwrap(), Delivery::Suppressed);
+
+        // whether the summary task or the repeat saw the window run out first, the summary is
+        // sent once and the repeat opened a window of its own
+        let mut texts: Vec<_> = received(&server, 3)
+            .await
+            .into_iter()
+            .map(|body| body["text"].as_str().unwrap().to_owned())
+            .collect();
+        texts.sort();
+        assert_eq!(
+            texts,
+            [
+                "db-1 is down",
+                "db-1 is down",
+                "db-1 is down\n_Repeated once in the last 10m_"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn store_keeps_expired_repeats() {
+        tokio::time::pause();
+        let store = MemoryStore::new();
+        let window = Duration::from_secs(600);
+        let payload = PayloadBuilder::new().text("db-1 is down").build().unwrap();
+
+        let first = match store.record("fp", &payload, window) {
+            Recorded::Opened { id, expired: None } => id,
+            recorded => panic!("expected a new window, got {:?}", recorded),
+        };
+        assert!(matches!(
+            store.record("fp", &payload, window),
+            Recorded::Suppressed
+        ));
+        tokio::time::advance(window).await;
+        match store.record("fp", &payload, window) {
+            Recorded::Opened {
+                expired: Some(expired),
+                ..
+            } => assert_eq!(expired.count, 1),
+            recorded => panic!("expected the expired repeat, got {:?}", recorded),
+        }
+        // the first window's summary task is late, and mustn't close the new window
+        assert!(store.close("fp", first).is_none());
+        assert!(matches!(
+            store.record("fp", &payload, window),
+            Recorded::Suppressed
+        ));
+    }
+
+    #[test]
+    fn fingerprints() {
+        let fingerprint = |fingerprint: Fingerprint, payload: &Payload| fingerprint.of(payload);
+        let firing = alert("HighLatency", "p99 is 2.1s");
+        let still_firing = alert("HighLatency", "p99 is 2.4s");
+
+        assert_ne!(
+            fingerprint(Fingerprint::default(), &firing),
+            fingerprint(Fingerprint::default(), &still_firing)
+        );
+        assert_eq!(
+            fingerprint(Fingerprint::AttachmentTitles, &firing),
+            fingerprint(Fingerprint::AttachmentTitles, &still_firing)
+        );
+
+        let elsewhere = Payload {
+            channel: Some("#other".to_owned()),
+            ..firing.clone()
+        };
+        assert_ne!(
+            fingerprint(Fingerprint::Text, &firing),
+            fingerprint(Fingerprint::Text, &elsewhere)
+        );
+
+        let by_key = Fingerprint::key(|_| "HighLatency/api".to_owned());
+        assert_eq!(by_key.of(&firing), by_key.of(&alert("other", "other")));
+    }
+
+    #[test]
+    fn formats_window() {
+        assert_eq!(format_window(Duration::from_secs(600)), "10m");
+        assert_eq!(format_window(Duration::from_secs(5400)), "1h 30m");
+        assert_eq!(format_window(Duration::from_millis(1500)), "1s");
+        assert_eq!(format_window(Duration::from_millis(200)), "200ms");
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{Attachment, HexColor, Payload, SlackText};
+use serde::{Deserialize, Serialize};
+#[cfg(any(feature = "client", feature = "ureq"))]
+use std::borrow::Cow;
+
+/// A chat system that accepts slack style webhook payloads
+///
+/// Mattermost, Rocket.Chat and Discord (through the `/slack` suffix on its webhook urls) all take
+/// slack's payloads, but each supports a different subset of them. Setting the dialect on a
+/// [`WebhookUrl`](crate::WebhookUrl) validates the url for that system and has the `Slack`
+/// clients [`adapt()`](Dialect::adapt) every payload before it is sent.
+///
+/// ```rust,no_run
+/// # #[cfg(feature = "client")]
+/// # fn run() -> slack_hook::Result<()> {
+/// use slack_hook::{Dialect, Slack, WebhookUrl};
+///
+/// let hook = WebhookUrl::parse_dialect(
+///     "https://chat.example.com/hooks/xa8pw3ukqfbwjbhfp5jsnwjx1a",
+///     Dialect::Mattermost,
+/// )?;
+/// let slack = Slack::new(hook)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dialect {
+    /// Slack itself, which supports every field
+    #[default]
+    Slack,
+    /// Mattermost's incoming webhooks, e.g. `https://chat.example.com/hooks/<key>`
+    Mattermost,
+    /// Rocket.Chat's incoming webhooks, e.g. `https://chat.example.com/hooks/<id>/<token>`
+    RocketChat,
+    /// Discord's slack compatible webhooks, e.g.
+    /// `https://discord.com/api/webhooks/<id>/<token>/slack`
+    Discord,
+}
+
+/// Most characters Mattermost allows in a post
+const MATTERMOST_MAX_TEXT: usize = 16_383;
+/// Most characters Discord allows in a message
+const DISCORD_MAX_TEXT: usize = 2_000;
+/// Most embeds, which attachments become, Discord allows in a message
+const DISCORD_MAX_ATTACHMENTS: usize = 10;
+/// Most fields Discord allows in an embed
+const DISCORD_MAX_FIELDS: usize = 25;
+
+impl Dialect {
+    /// Adapt a payload for this chat system
+    ///
+    /// Every dialect other than slack:
+    ///
+    /// - drops `blocks`, `metadata`, `link_names`, `parse` and the unfurl options, along with
+    ///   attachment `actions`, `callback_id` and `mrkdwn_in`. Markdown is always rendered
+    /// - converts the slack colors, e.g. `good`, to their hex codes
+    ///
+    /// On top of that, Mattermost and Rocket.Chat drop attachment timestamps, while Discord drops
+    /// `channel` and `icon_emoji` and keeps to its limits on text length, attachments and fields.
+    pub fn adapt(&self, payload: &Payload) -> Payload {
+        if *self == Dialect::Slack {
+            return payload.clone();
+        }
+
+        let mut payload = Payload {
+            blocks: None,
+            metadata: None,
+            link_names: None,
+            parse: None,
+            unfurl_links: None,
+            unfurl_media: None,
+            ..payload.clone()
+        };
+        for attachment in payload.attachments.iter_mut().flatten() {
+            self.adapt_attachment(attachment);
+        }
+
+        match self {
+            Dialect::Slack => {}
+            Dialect::Mattermost => {
+                payload.text = payload
+                    .text
+                    .map(|text| truncate(&text, MATTERMOST_MAX_TEXT));
+            }
+            Dialect::RocketChat => {}
+            Dialect::Discord => {
+                payload.channel = None;
+                payload.icon_emoji = None;
+                payload.text = payload.text.map(|text| truncate(&text, DISCORD_MAX_TEXT));
+                if let Some(attachments) = &mut payload.attachments {
+                    attachments.truncate(DISCORD_MAX_ATTACHMENTS);
+                }
+            }
+        }
+        payload
+    }
+
+    fn adapt_attachment(&self, attachment: &mut Attachment) {
+        attachment.actions = None;
+        attachment.callback_id = None;
+        attachment.mrkdwn_in = None;
+        attachment.color = attachment.color.map(|color| {
+            let (red, green, blue) = color.rgb();
+            HexColor::from_rgb(red, green, blue)
+        });
+
+        match self {
+            Dialect::Slack => {}
+            Dialect::Mattermost | Dialect::RocketChat => attachment.ts = None,
+            Dialect::Discord => {
+                if let Some(fields) = &mut attachment.fields {
+                    fields.truncate(DISCORD_MAX_FIELDS);
+                }
+            }
+        }
+    }
+}
+
+/// The payload a client sends: merged with its defaults and then adapted to its dialect
+#[cfg(any(feature = "client", feature = "ureq"))]
+pub(crate) fn outgoing<'a>(
+    payload: &'a Payload,
+    defaults: Option<&Payload>,
+    dialect: Dialect,
+) -> Cow<'a, Payload> {
+    let payload = match defaults {
+        Some(defaults) => Cow::Owned(payload.with_defaults(defaults)),
+        None => Cow::Borrowed(payload),
+    };
+    match dialect {
+        Dialect::Slack => payload,
+        dialect => Cow::Owned(dialect.adapt(&payload)),
+    }
+}
+
+/// Cut text down to `max` characters, ending with an ellipsis
+///
+/// The text is already escaped, so the cut is moved back rather than split an entity like `&amp;`.
+fn truncate(text: &SlackText, max: usize) -> SlackText {
+    let text = text.to_string();
+    if text.chars().count() <= max {
+        return SlackText::new_raw(text);
+    }
+
+    let mut cut = text
+        .char_indices()
+        .nth(max - 1)
+        .map_or(text.len(), |(i, _)| i);
+    if let Some(entity) = text[..cut].rfind('&') {
+        if !text[entity..cut].contains(';') {
+            cut = entity;
+        }
+    }
+    SlackText::new_raw(format!("{}…", &text[..cut]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        Action, AttachmentBuilder, Block, Field, MessageMetadata, PayloadBuilder, Section,
+        SlackColor, SlackTime, TextObject,
+    };
+    use insta::assert_json_snapshot;
+
+    fn payload() -> Payload {
+        let attachment = AttachmentBuilder::new("Deploy failed")
+            .color(SlackColor::Danger)
+            .text("*api* failed its health checks")
+            .markdown_in(&[Section::Text])
+            .fields(vec![Field::new("env", "prod", Some(true))])
+            .actions(vec![Action::new(
+                "button",
+                "Roll back",
+                "rollback",
+                None,
+                None,
+            )])
+            .callback_id("deploys")
+            .ts(SlackTime::from_unix(1_714_564_800, 0))
+            .build()
+            .unwrap();
+        PayloadBuilder::new()
+            .text("Deploy <failed>")
+            .channel("#deploys")
+            .icon_emoji(":rocket:")
+            .attachments(vec![attachment])
+            .blocks(vec![Block::Section {
+                text: Some(TextObject::plain("Deploy failed")),
+                fields: None,
+                accessory: None,
+            }])
+            .metadata(MessageMetadata::new("deploy_failed", "api"))
+            .link_names(true)
+            .unfurl_links(false)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn slack_is_unchanged() {
+        let payload = payload();
+        assert_eq!(
+            serde_json::to_value(Dialect::Slack.adapt(&payload)).unwrap(),
+            serde_json::to_value(&payload).unwrap()
+        );
+    }
+
+    #[test]
+    fn mattermost() {
+        assert_json_snapshot!(Dialect::Mattermost.adapt(&payload()), @r###"
+        {
+          "text": "Deploy &lt;failed&gt;",
+          "channel": "#deploys",
+          "icon_emoji": ":rocket:",
+          "attachments": [
+            {
+              "fallback": "Deploy failed",
+              "text": "*api* failed its health checks",
+              "color": "#a30200",
+              "fields": [
+                {
+                  "title": "env",
+                  "value": "prod",
+                  "short": true
+                }
+              ]
+            }
+          ]
+        }
+        "###);
+    }
+
+    #[test]
+    fn discord() {
+        assert_json_snapshot!(Dialect::Discord.adapt(&payload()), @r###"
+        {
+          "text": "Deploy &lt;failed&gt;",
+          "attachments": [
+            {
+              "fallback": "Deploy failed",
+              "text": "*api* failed its health checks",
+              "color": "#a30200",
+              "fields": [
+                {
+                  "title": "env",
+                  "value": "prod",
+                  "short": true
+                }
+              ],
+              "ts": 1714564800
+            }
+          ]
+        }
+        "###);
+    }
+
+    #[test]
+    fn discord_limits() {
+        let fields = (0..30)
+            .map(|i| Field::new(i.to_string(), "value", Some(true)))
+            .collect();
+        let attachment = AttachmentBuilder::new("many fields")
+            .fields(fields)
+            .build()
+            .unwrap();
+        let payload = PayloadBuilder::new()
+            .text(format!("{}&", "a".repeat(1_998)))
+            .attachments(vec![attachment; 12])
+            .build()
+            .unwrap();
+
+        let adapted = Dialect::Discord.adapt(&payload);
+        let text = adapted.text.unwrap().to_string();
+        assert_eq!(text, format!("{}…", "a".repeat(1_998)));
+        let attachments = adapted.attachments.unwrap();
+        assert_eq!(attachments.len(), DISCORD_MAX_ATTACHMENTS);
+        assert_eq!(
+            attachments[0].fields.as_ref().unwrap().len(),
+            DISCORD_MAX_FIELDS
+        );
+    }
+}
@@ -0,0 +1,548 @@
+//! Batch low-priority notifications into periodic summaries
+//!
+//! A [`Digest`] collects payloads and events instead of sending each one. Every interval, or once
+//! it holds enough items, it sends a single message that counts them by group, with the details
+//! of each group in an attachment that slack collapses when it runs long.
+//!
+//! ```rust,no_run
+//! # async fn run() -> slack_hook::Result<()> {
+//! use slack_hook::digest::Digest;
+//! use slack_hook::Slack;
+//! use std::time::Duration;
+//!
+//! let digest = Digest::new(Slack::from_env("SLACK_WEBHOOK_URL")?, Duration::from_secs(60 * 60))
+//!     .max_items(500)
+//!     .title("Batch jobs");
+//! digest.push_event("nightly-export finished", "took 4m 12s").await?;
+//! digest.push_event("nightly-export finished", "took 3m 58s").await?;
+//! digest.push_event("reindex finished", "").await?;
+//!
+//! // send whatever is left before exiting
+//! digest.flush().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::background::{ignore_errors, ErrorHandler};
+use crate::{Attachment, Error, Payload, Result, Slack, SlackText};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt, mem,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+type GroupFn = Arc<dyn Fn(&Payload) -> String + Send + Sync>;
+
+/// Most groups given an attachment, the largest first
+const MAX_GROUPS: usize = 20;
+/// Most details listed in a group's attachment
+const MAX_DETAILS: usize = 10;
+
+/// Collects payloads and events, sending them as one summary message per batch
+///
+/// A batch is sent [`interval`](Digest::new) after its first item, or as soon as it reaches
+/// [`max_items`](Digest::max_items). Each batch's interval is timed by a spawned task, so pushing
+/// outside a Tokio runtime panics.
+#[derive(Clone)]
+pub struct Digest {
+    slack: Arc<Slack>,
+    interval: Duration,
+    max_items: usize,
+    title: String,
+    group_by: Option<GroupFn>,
+    batch: Arc<Mutex<Batch>>,
+    on_error: ErrorHandler,
+}
+
+#[derive(Debug, Default)]
+struct Batch {
+    groups: Vec<Group>,
+    /// The position of each group in `groups`, by its name
+    index: HashMap<String, usize>,
+    items: usize,
+    /// Counts the batches sent, so a timer only sends the batch it was started for
+    generation: u64,
+}
+
+#[derive(Debug)]
+struct Group {
+    name: SlackText,
+    count: usize,
+    /// The first details, up to the most that are listed
+    details: Vec<SlackText>,
+    /// How many details there were past those
+    more_details: usize,
+}
+
+impl Group {
+    fn add_detail(&mut self, detail: SlackText) {
+        if self.details.len() < MAX_DETAILS {
+            self.details.push(detail);
+        } else {
+            self.more_details += 1;
+        }
+    }
+}
+
+impl Batch {
+    /// The group named `name`, added if it is new
+    fn group(&mut self, name: SlackText) -> &mut Group {
+        let groups = &mut self.groups;
+        let index = *self.index.entry(name.to_string()).or_insert_with(|| {
+            groups.push(Group {
+                name,
+                count: 0,
+                details: Vec::new(),
+                more_details: 0,
+            });
+            groups.len() - 1
+        });
+        &mut self.groups[index]
+    }
+}
+
+impl Digest {
+    /// Construct a digest that sends its batches with `slack`, `interval` after their first item
+    pub fn new(slack: Slack, interval: Duration) -> Digest {
+        Digest {
+            slack: Arc::new(slack),
+            interval,
+            max_items: usize::MAX,
+            title: "Digest".to_owned(),
+            group_by: None,
+            batch: Arc::default(),
+            on_error: ignore_errors(),
+        }
+    }
+
+    /// Send a batch as soon as it holds `max_items`, rather than waiting out the interval
+    pub fn max_items(mut self, max_items: usize) -> Digest {
+        self.max_items = max_items.max(1);
+        self
+    }
+
+    /// Set the title the summaries start with, `Digest` by default
+    pub fn title<S: Into<String>>(mut self, title: S) -> Digest {
+        self.title = title.into();
+        self
+    }
+
+    /// Group pushed payloads by the key `group_by` makes, instead of by their text
+    pub fn group_by<F>(mut self, group_by: F) -> Digest
+    where
+        F: Fn(&Payload) -> String + Send + Sync + 'static,
+    {
+        self.group_by = Some(Arc::new(group_by));
+        self
+    }
+
+    /// Pass `on_error` the errors from batches sent when their interval ends
+    ///
+    /// Batches sent by [`push`](Digest::push) or [`flush`](Digest::flush) return their errors
+    /// instead.
+    pub fn on_error<F>(mut self, on_error: F) -> Digest
+    where
+        F: Fn(Error) + Send + Sync + 'static,
+    {
+        self.on_error = Arc::new(on_error);
+        self
+    }
+
+    /// Add a payload to the batch
+    ///
+    /// Payloads are grouped by their text, or their first attachment's fallback when they have no
+    /// text. With [`group_by`](Digest::group_by), that text is listed as a detail of the group.
+    ///
+    /// Returns the error from sending the batch if this payload filled it.
+    pub async fn push(&self, payload: &Payload) -> Result<()> {
+        let text = payload.text.clone().or_else(|| {
+            let attachments = payload.attachments.as_ref()?;
+            attachments.first().map(|first| first.fallback.clone())
+        });
+        match &self.group_by {
+            Some(group_by) => self.add(SlackText::new(group_by(payload)), text).await,
+            None => {
+                let name = text.unwrap_or_else(|| SlackText::new("(no text)"));
+                self.add(name, None).await
+            }
+        }
+    }
+
+    /// Add an event to the batch, counted under `group` and listing `detail` unless it is empty
+    ///
+    /// Returns the error from sending the batch if this event filled it.
+    pub async fn push_event<G, D>(&self, group: G, detail: D) -> Result<()>
+    where
+        G: Into<String>,
+        D: Into<String>,
+    {
+        let detail = detail.into();
+        let detail = Some(detail).filter(|detail| !detail.is_empty());
+        self.add(SlackText::new(group.into()), detail.map(SlackText::new))
+            .await
+    }
+
+    /// Send the batch now, if it holds anything
+    ///
+    /// When sending fails the batch is kept, to go out with the next one.
+    pub async fn flush(&self) -> Result<()> {
+        match self.take(None) {
+            Some(groups) => self.send(groups).await,
+            None => Ok(()),
+        }
+    }
+
+    /// The number of items waiting in the batch
+    pub fn pending(&self) -> usize {
+        self.batch().items
+    }
+
+    async fn add(&self, name: SlackText, detail: Option<SlackText>) -> Result<()> {
+        let (full, started) = {
+            let mut batch = self.batch();
+            let group = batch.group(name);
+            group.count += 1;
+            if let Some(detail) = detail {
+                group.add_detail(detail);
+            }
+            batch.items += 1;
+            let started = (batch.items == 1).then_some(batch.generation);
+            (batch.items >= self.max_items, started)
+        };
+
+        if full {
+            return self.flush().await;
+        }
+        if let Some(generation) = started {
+            self.start_timer(generation);
+        }
+        Ok(())
+    }
+
+    /// Send the batch `generation` once the interval is up, unless it has been sent by then
+    fn start_timer(&self, generation: u64) {
+        let digest = self.clone();
+        let _timer = tokio::spawn(async move {
+            tokio::time::sleep(digest.interval).await;
+            if let Some(groups) = digest.take(Some(generation)) {
+                if let Err(err) = digest.send(groups).await {
+                    (digest.on_error)(err);
+                }
+            }
+        });
+    }
+
+    /// Send taken groups, putting them back into the batch if that fails
+    async fn send(&self, groups: Vec<Group>) -> Result<()> {
+        let summary = self.summary(&groups);
+        let result = self.slack.send(&summary).await;
+        if result.is_err() {
+            self.restore(groups);
+        }
+        result
+    }
+
+    /// Merge groups that failed to send back into the batch
+    fn restore(&self, groups: Vec<Group>) {
+        let generation = {
+            let mut batch = self.batch();
+            let was_empty = batch.items == 0;
+            for restored in groups {
+                batch.items += restored.count;
+                let group = batch.group(restored.name);
+                group.count += restored.count;
+                group.more_details += restored.more_details;
+                for detail in restored.details {
+                    group.add_detail(detail);
+                }
+            }
+            // otherwise the batch's own timer sends them
+            was_empty.then_some(batch.generation)
+        };
+        if let Some(generation) = generation {
+            self.start_timer(generation);
+        }
+    }
+
+    /// Take the groups out of the batch, unless it is empty or, given a `generation`, has already
+    /// been sent
+    fn take(&self, generation: Option<u64>) -> Option<Vec<Group>> {
+        let mut batch = self.batch();
+        if batch.items == 0 || generation.map_or(false, |g| g != batch.generation) {
+            return None;
+        }
+        batch.items = 0;
+        batch.generation += 1;
+        batch.index.clear();
+        Some(mem::take(&mut batch.groups))
+    }
+
+    fn batch(&self) -> MutexGuard<'_, Batch> {
+        self.batch
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn summary(&self, groups: &[Group]) -> Payload {
+        let total: usize = groups.iter().map(|group| group.count).sum();
+        let mut text = format!(
+            "*{}*: {} in {}",
+            SlackText::new(self.title.as_str()),
+            plural(total, "message"),
+            plural(groups.len(), "group"),
+        );
+        if groups.len() > MAX_GROUPS {
+            text.push_str(&format!(", showing the largest {MAX_GROUPS}"));
+        }
+
+        // stable, so groups of the same size stay in the order they were first seen
+        let mut largest: Vec<&Group> = groups.iter().collect();
+        largest.sort_by_key(|group| Reverse(group.count));
+        let attachments = largest
+            .into_iter()
+            .take(MAX_GROUPS)
+            .map(|group| {
+                let heading = SlackText::new_raw(format!("{} ({})", group.name, group.count));
+                let mut lines: Vec<String> = group
+                    .details
+                    .iter()
+                    .map(|detail| format!("• {detail}"))
+                    .collect();
+                if group.more_details > 0 {
+                    lines.push(format!("…and {} more", group.more_details));
+                }
+
+                Attachment {
+                    fallback: heading.clone(),
+                    title: Some(heading),
+                    text: Some(lines.join("\n"))
+                        .filter(|text| !text.is_empty())
+                        .map(SlackText::new_raw),
+                    ..Attachment::default()
+                }
+            })
+            .collect();
+
+        Payload {
+            text: Some(SlackText::new_raw(text)),
+            attachments: Some(attachments),
+            ..Payload::default()
+        }
+    }
+}
+
+impl fmt::Debug for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Digest")
+            .field("slack", &self.slack)
+            .field("interval", &self.interval)
+            .field("max_items", &self.max_items)
+            .field("title", &self.title)
+            .field("pending", &self.pending())
+            .finish_non_exhaustive()
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {noun}"),
+        count => format!("{count} {noun}s"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{hook_url, received, respond_with};
+    use crate::PayloadBuilder;
+    use insta::{assert_json_snapshot, assert_snapshot};
+    use std::sync::mpsc;
+    use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+    /// Answer the first post with a 500
+    async fn fail_once(server: &MockServer) {
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(server)
+            .await;
+    }
+
+    async fn digest(server: &MockServer, interval: Duration) -> Digest {
+        respond_with(server, 200).await;
+        Digest::new(Slack::new(hook_url(server)).unwrap(), interval)
+    }
+
+    #[tokio::test]
+    async fn sends_after_interval() {
+        let server = MockServer::start().await;
+        let digest = digest(&server, Duration::from_millis(200))
+            .await
+            .title("Jobs <prod>");
+
+        let finished = PayloadBuilder::new().text("job finished").build().unwrap();
+        for _ in 0..3 {
+            digest.push(&finished).await.unwrap();
+        }
+        digest
+            .push_event("export failed", "disk <full>")
+            .await
+            .unwrap();
+        assert_eq!(digest.pending(), 4);
+        assert!(server.received_requests().await.unwrap().is_empty());
+
+        tokio::time::pause();
+        let bodies = received(&server, 1).await;
+        assert_eq!(digest.pending(), 0);
+        assert_json_snapshot!(bodies, @r###"
+        [
+          {
+            "attachments": [
+              {
+                "fallback": "job finished (3)",
+                "title": "job finished (3)"
+              },
+              {
+                "fallback": "export failed (1)",
+                "text": "• disk &lt;full&gt;",
+                "title": "export failed (1)"
+              }
+            ],
+            "text": "*Jobs &lt;prod&gt;*: 4 messages in 2 groups"
+          }
+        ]
+        "###);
+    }
+
+    #[tokio::test]
+    async fn sends_when_full() {
+        let server = MockServer::start().await;
+        let digest = digest(&server, Duration::from_millis(300))
+            .await
+            .max_items(2)
+            .group_by(|payload| payload.channel.clone().unwrap_or_default());
+
+        for channel in ["#a", "#b", "#a"] {
+            let payload = PayloadBuilder::new()
+                .channel(channel)
+                .text(format!("ping {channel}"))
+                .build()
+                .unwrap();
+            digest.push(&payload).await.unwrap();
+        }
+        // the first two were sent together, and the third waits for its own interval
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+        tokio::time::pause();
+        assert_json_snapshot!(received(&server, 2).await, @r###"
+        [
+          {
+            "attachments": [
+              {
+                "fallback": "#a (1)",
+                "text": "• ping #a",
+                "title": "#a (1)"
+              },
+              {
+                "fallback": "#b (1)",
+                "text": "• ping #b",
+                "title": "#b (1)"
+              }
+            ],
+            "text": "*Digest*: 2 messages in 2 groups"
+          },
+          {
+            "attachments": [
+              {
+                "fallback": "#a (1)",
+                "text": "• ping #a",
+                "title": "#a (1)"
+              }
+            ],
+            "text": "*Digest*: 1 message in 1 group"
+          }
+        ]
+        "###);
+    }
+
+    #[tokio::test]
+    async fn flush() {
+        let server = MockServer::start().await;
+        let digest = digest(&server, Duration::from_secs(3600)).await;
+
+        digest.flush().await.unwrap();
+        assert!(server.received_requests().await.unwrap().is_empty());
+
+        for i in 0..12 {
+            digest
+                .push_event("backup", format!("db-{i}"))
+                .await
+                .unwrap();
+        }
+        digest.flush().await.unwrap();
+        assert_json_snapshot!(received(&server, 1).await, @r###"
+        [
+          {
+            "attachments": [
+              {
+                "fallback": "backup (12)",
+                "text": "• db-0\n• db-1\n• db-2\n• db-3\n• db-4\n• db-5\n• db-6\n• db-7\n• db-8\n• db-9\n…and 2 more",
+                "title": "backup (12)"
+              }
+            ],
+            "text": "*Digest*: 12 messages in 1 group"
+          }
+        ]
+        "###);
+    }
+
+    #[tokio::test]
+    async fn keeps_batch_when_flush_fails() {
+        let server = MockServer::start().await;
+        fail_once(&server).await;
+        let digest = digest(&server, Duration::from_secs(3600)).await;
+
+        digest.push_event("backup", "db-1").await.unwrap();
+        let err = digest.flush().await.unwrap_err();
+        assert_snapshot!(err, @"slack service error: HTTP error 500 Internal Server Error");
+        assert_eq!(digest.pending(), 1);
+
+        digest.push_event("backup", "db-2").await.unwrap();
+        digest.flush().await.unwrap();
+        let bodies = received(&server, 2).await;
+        assert_json_snapshot!(bodies[1], @r###"
+        {
+          "attachments": [
+            {
+              "fallback": "backup (2)",
+              "text": "• db-1\n• db-2",
+              "title": "backup (2)"
+            }
+          ],
+          "text": "*Digest*: 2 messages in 1 group"
+        }
+        "###);
+    }
+
+    #[tokio::test]
+    async fn retries_batch_after_interval() {
+        let server = MockServer::start().await;
+        fail_once(&server).await;
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let digest = digest(&server, Duration::from_secs(60))
+            .await
+            .on_error(move |err| tx.lock().unwrap().send(err.to_string()).unwrap());
+
+        tokio::time::pause();
+        digest.push_event("backup", "db-1").await.unwrap();
+        let bodies = received(&server, 2).await;
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            ["slack service error: HTTP error 500 Internal Server Error"]
+        );
+        assert_eq!(bodies[0], bodies[1]);
+        assert_eq!(digest.pending(), 0);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fmt, str::Utf8Error};
+use std::{convert::Infallible, fmt, str::Utf8Error, time::Duration};
 
 use hex::FromHexError;
 
@@ -10,8 +10,28 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
     /// slack service error
     Slack(String),
+    /// slack refused the payload itself with a `4xx` status, e.g. `invalid_payload`, so sending it
+    /// again won't help
+    Rejected(String),
+    /// slack web api error, holding the `error` code from the response
+    Api(String),
+    /// slack is rate limiting the webhook or Web API method, holding how long it asked to wait if
+    /// it said
+    RateLimited(Option<Duration>),
+    /// Microsoft Teams service error
+    Teams(String),
     /// Hex color parsing error
     HexColor(String),
+    /// Webhook url validation error
+    WebhookUrl(String),
+    /// Missing or invalid configuration
+    Config(String),
+    /// Cron expression parsing error
+    Cron(String),
+    /// Variables that a Workflow Builder webhook won't accept
+    Workflow(String),
+    /// Outbox journal error, e.g. a full or corrupt journal
+    Outbox(String),
     /// utf8 error, slack responses should be valid utf8
     Utf8(Utf8Error),
     /// `serde_json::Error`
@@ -19,11 +39,56 @@ pub enum Error {
     /// `hex::FromHexError`
     FromHex(FromHexError),
     /// `reqwest::Error`
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
     Reqwest(reqwest::Error),
+    /// `ureq::Error`
+    #[cfg(feature = "ureq")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ureq")))]
+    Ureq(Box<ureq::Error>),
     /// `url::ParseError`
     Url(url::ParseError),
     /// `std::io::Error`
     Io(std::io::Error),
+    /// `toml::de::Error`
+    #[cfg(feature = "config")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "config")))]
+    Toml(toml::de::Error),
+    /// `tungstenite::Error`
+    #[cfg(feature = "socket-mode")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "socket-mode")))]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+}
+
+impl Error {
+    /// The error for a `429 Too Many Requests` response, given its `Retry-After` header
+    #[cfg(any(feature = "client", feature = "ureq"))]
+    pub(crate) fn rate_limited(retry_after: Option<&str>) -> Error {
+        let secs = retry_after.and_then(|secs| secs.trim().parse().ok());
+        Error::RateLimited(secs.map(Duration::from_secs))
+    }
+
+    /// The error for any other unsuccessful response, given its status and body
+    ///
+    /// Slack explains a `4xx` in the body, e.g. `no_service` for a removed webhook.
+    #[cfg(any(feature = "client", feature = "ureq"))]
+    pub(crate) fn http_status<S: fmt::Display>(status: S, client_error: bool, body: &str) -> Error {
+        let message = match body.trim() {
+            "" => format!("HTTP error {status}"),
+            body => format!("HTTP error {status}: {body}"),
+        };
+        if client_error {
+            Error::Rejected(message)
+        } else {
+            Error::Slack(message)
+        }
+    }
+}
+
+impl From<Infallible> for Error {
+    fn from(infallible: Infallible) -> Self {
+        match infallible {}
+    }
 }
 
 impl From<Utf8Error> for Error {
@@ -44,12 +109,20 @@ impl From<FromHexError> for Error {
     }
 }
 
+#[cfg(feature = "client")]
 impl From<reqwest::Error> for Error {
     fn from(reqwest_err: reqwest::Error) -> Self {
         Self::Reqwest(reqwest_err)
     }
 }
 
+#[cfg(feature = "ureq")]
+impl From<ureq::Error> for Error {
+    fn from(ureq_err: ureq::Error) -> Self {
+        Self::Ureq(Box::new(ureq_err))
+    }
+}
+
 impl From<url::ParseError> for Error {
     fn from(url_err: url::ParseError) -> Self {
         Self::Url(url_err)
@@ -62,17 +135,50 @@ impl From<std::io::Error> for Error {
     }
 }
 
+#[cfg(feature = "config")]
+impl From<toml::de::Error> for Error {
+    fn from(toml_err: toml::de::Error) -> Self {
+        Self::Toml(toml_err)
+    }
+}
+
+#[cfg(feature = "socket-mode")]
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(ws_err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(ws_err))
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Slack(err) => write!(f, "slack service error: {err}"),
+            Self::Rejected(err) => write!(f, "slack rejected the payload: {err}"),
+            Self::Api(err) => write!(f, "slack api error: {err}"),
+            Self::RateLimited(Some(wait)) => {
+                write!(f, "slack rate limit hit, retry after {}s", wait.as_secs())
+            }
+            Self::RateLimited(None) => write!(f, "slack rate limit hit"),
+            Self::Teams(err) => write!(f, "teams service error: {err}"),
             Self::HexColor(err) => write!(f, "hex color parsing error: {err}"),
+            Self::WebhookUrl(err) => write!(f, "invalid webhook url: {err}"),
+            Self::Config(err) => write!(f, "configuration error: {err}"),
+            Self::Cron(err) => write!(f, "invalid cron expression: {err}"),
+            Self::Workflow(err) => write!(f, "invalid workflow variables: {err}"),
+            Self::Outbox(err) => write!(f, "outbox error: {err}"),
             Self::Utf8(err) => err.fmt(f),
             Self::Serialize(err) => err.fmt(f),
             Self::FromHex(err) => err.fmt(f),
+            #[cfg(feature = "client")]
             Self::Reqwest(err) => err.fmt(f),
+            #[cfg(feature = "ureq")]
+            Self::Ureq(err) => err.fmt(f),
             Self::Url(err) => err.fmt(f),
             Self::Io(err) => err.fmt(f),
+            #[cfg(feature = "config")]
+            Self::Toml(err) => err.fmt(f),
+            #[cfg(feature = "socket-mode")]
+            Self::WebSocket(err) => err.fmt(f),
         }
     }
 }
@@ -2,32 +2,163 @@ use crate::error::Error;
 use std::{convert::TryFrom, fmt, str::FromStr};
 
 use hex::FromHex;
-use serde::Serialize;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-/// A `HexColor` `String` can be one of:
+mod css;
+
+/// A `HexColor` can be one of:
 ///
 /// 1. `String`s: `good`, `warning`, `danger`
 /// 2. Any valid hex color code: e.g. `#b13d41` or `#000`.
+/// 3. An `rgb()` color: e.g. `rgb(177, 61, 65)`
+/// 4. A CSS named color: e.g. `rebeccapurple`
 ///
-/// hex color codes will be checked to ensure a valid hex number is provided
-#[derive(Serialize, Debug, Clone, PartialEq)]
-pub struct HexColor(String);
+/// hex color codes will be checked to ensure a valid hex number is provided. Everything other than
+/// the slack colors is normalized to the `#rrggbb` form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HexColor(Color);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Color {
+    Slack(SlackColor),
+    Rgb([u8; 3]),
+}
 
 impl HexColor {
-    fn new<S: Into<String>>(s: S) -> HexColor {
-        HexColor(s.into())
+    /// Construct a color from its red, green and blue components
+    pub const fn from_rgb(red: u8, green: u8, blue: u8) -> HexColor {
+        HexColor(Color::Rgb([red, green, blue]))
+    }
+
+    /// The red, green and blue components of the color
+    ///
+    /// The slack colors use the values that slack currently renders them with.
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        let [r, g, b] = self.components();
+        (r, g, b)
+    }
+
+    /// The red component of the color
+    pub fn red(&self) -> u8 {
+        self.components()[0]
+    }
+
+    /// The green component of the color
+    pub fn green(&self) -> u8 {
+        self.components()[1]
+    }
+
+    /// The blue component of the color
+    pub fn blue(&self) -> u8 {
+        self.components()[2]
+    }
+
+    /// Mix the color with white, where an `amount` of `0.0` leaves the color unchanged and `1.0`
+    /// is white
+    pub fn lighten(&self, amount: f32) -> HexColor {
+        self.interpolate(&HexColor::from_rgb(255, 255, 255), amount)
+    }
+
+    /// Mix the color with black, where an `amount` of `0.0` leaves the color unchanged and `1.0`
+    /// is black
+    pub fn darken(&self, amount: f32) -> HexColor {
+        self.interpolate(&HexColor::from_rgb(0, 0, 0), amount)
+    }
+
+    /// Linearly interpolate between this color at `t = 0.0` and `other` at `t = 1.0`
+    ///
+    /// `t` is clamped to `0.0..=1.0`.
+    pub fn interpolate(&self, other: &HexColor, t: f32) -> HexColor {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let (from, to) = (self.components(), other.components());
+        let mix = |i: usize| {
+            let (from, to) = (f32::from(from[i]), f32::from(to[i]));
+            (from + (to - from) * t).round() as u8
+        };
+        HexColor::from_rgb(mix(0), mix(1), mix(2))
+    }
+
+    /// Pick a color along an evenly spaced gradient of `stops`, where `t = 0.0` is the first stop
+    /// and `t = 1.0` is the last
+    ///
+    /// ```
+    /// use slack_hook::HexColor;
+    ///
+    /// let stops = ["green", "yellow", "red"].map(|c| c.parse::<HexColor>().unwrap());
+    /// let error_rate = 0.25;
+    /// assert_eq!(HexColor::gradient(&stops, error_rate).to_string(), "#80c000");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `stops` is empty
+    pub fn gradient(stops: &[HexColor], t: f32) -> HexColor {
+        assert!(!stops.is_empty(), "a gradient needs at least one stop");
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let segments = stops.len() - 1;
+        let position = t * segments as f32;
+        let i = (position.floor() as usize).min(segments.saturating_sub(1));
+        match stops.get(i + 1) {
+            Some(next) => stops[i].interpolate(next, position - i as f32),
+            None => stops[i],
+        }
+    }
+
+    /// A severity palette running from `good` at `0.0` through `warning` to `danger` at `1.0`
+    ///
+    /// Handy for coloring an attachment by something like an error rate.
+    pub fn severity(t: f32) -> HexColor {
+        HexColor::gradient(
+            &[
+                SlackColor::Good.into(),
+                SlackColor::Warning.into(),
+                SlackColor::Danger.into(),
+            ],
+            t,
+        )
+    }
+
+    fn components(&self) -> [u8; 3] {
+        match self.0 {
+            Color::Slack(SlackColor::Good) => [0x2e, 0xb8, 0x86],
+            Color::Slack(SlackColor::Warning) => [0xda, 0xa0, 0x38],
+            Color::Slack(SlackColor::Danger) => [0xa3, 0x02, 0x00],
+            Color::Rgb(rgb) => rgb,
+        }
     }
 }
 
 impl Default for HexColor {
     fn default() -> HexColor {
-        HexColor::new("#000")
+        HexColor::from_rgb(0, 0, 0)
     }
 }
 
 impl fmt::Display for HexColor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+        match self.0 {
+            Color::Slack(color) => color.fmt(f),
+            Color::Rgb([r, g, b]) => write!(f, "#{r:02x}{g:02x}{b:02x}"),
+        }
+    }
+}
+
+impl Serialize for HexColor {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for HexColor {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
     }
 }
 
@@ -42,7 +173,7 @@ impl TryFrom<&str> for HexColor {
 
 /// Default slack colors built-in to the API
 /// See: <https://api.slack.com/docs/attachments>
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum SlackColor {
     /// green
     Good,
@@ -52,7 +183,7 @@ pub enum SlackColor {
     Danger,
 }
 
-const SLACK_COLORS: [&str; 3] = ["good", "warning", "danger"];
+const SLACK_COLORS: [SlackColor; 3] = [SlackColor::Good, SlackColor::Warning, SlackColor::Danger];
 
 impl fmt::Display for SlackColor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -72,7 +203,7 @@ impl AsRef<str> for SlackColor {
 
 impl From<SlackColor> for HexColor {
     fn from(color: SlackColor) -> HexColor {
-        HexColor::new(color.to_string())
+        HexColor(Color::Slack(color))
     }
 }
 
@@ -80,9 +211,20 @@ impl FromStr for HexColor {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s: String = s.into();
-        if SLACK_COLORS.contains(&&s[..]) {
-            return Ok(HexColor(s));
+        if let Some(color) = SLACK_COLORS.iter().find(|color| color.as_ref() == s) {
+            return Ok((*color).into());
+        }
+        if let Some([r, g, b]) = css::lookup(&s.to_ascii_lowercase()) {
+            return Ok(HexColor::from_rgb(r, g, b));
+        }
+        if let Some(args) = s.strip_prefix("rgb(").and_then(|s| s.strip_suffix(')')) {
+            return parse_rgb_args(args)
+                .map(|[r, g, b]| HexColor::from_rgb(r, g, b))
+                .ok_or_else(|| {
+                    Error::HexColor(format!(
+                        "Expected `rgb(<0-255>, <0-255>, <0-255>)`: found `{s}`"
+                    ))
+                });
         }
 
         let num_chars = s.chars().count();
@@ -105,17 +247,25 @@ impl FromStr for HexColor {
                 s
             })
         } else {
-            s.clone()
+            s.to_owned()
         };
 
         // see if the remaining part of the string is actually hex
-        match Vec::from_hex(&hex[1..]) {
-            Ok(_) => Ok(HexColor::new(s)),
-            Err(e) => Err(e.into()),
-        }
+        let [r, g, b] = <[u8; 3]>::from_hex(&hex[1..])?;
+        Ok(HexColor::from_rgb(r, g, b))
     }
 }
 
+fn parse_rgb_args(args: &str) -> Option<[u8; 3]> {
+    let mut components = args.split(',').map(|c| c.trim().parse::<u8>());
+    let rgb = [
+        components.next()?.ok()?,
+        components.next()?.ok()?,
+        components.next()?.ok()?,
+    ];
+    components.next().is_none().then_some(rgb)
+}
+
 #[cfg(test)]
 mod test {
     use std::convert::TryFrom;
@@ -151,6 +301,15 @@ mod test {
             let err = HexColor::try_from("#abc12z").unwrap_err();
             assert_snapshot!(err, @"Invalid character 'z' at position 5");
         }
+
+        #[test]
+        fn rgb_out_of_range() {
+            let err = HexColor::try_from("rgb(256, 0, 0)").unwrap_err();
+            assert_snapshot!(
+                err,
+                @"hex color parsing error: Expected `rgb(<0-255>, <0-255>, <0-255>)`: found `rgb(256, 0, 0)`"
+            );
+        }
     }
 
     mod ok {
@@ -174,17 +333,66 @@ mod test {
 
         #[test]
         fn short_hex() {
-            assert_hexcolor_roundtrip("#d18");
+            let h: HexColor = "#d18".parse().unwrap();
+            assert_snapshot!(h, @"#dd1188");
         }
 
         #[test]
         fn upper_hex() {
-            assert_hexcolor_roundtrip("#103D18");
+            let h: HexColor = "#103D18".parse().unwrap();
+            assert_snapshot!(h, @"#103d18");
         }
 
         #[test]
         fn lower_hex() {
             assert_hexcolor_roundtrip("#103d18");
         }
+
+        #[test]
+        fn from_rgb() {
+            let h = HexColor::from_rgb(177, 61, 65);
+            assert_snapshot!(h, @"#b13d41");
+            assert_eq!(h.rgb(), (177, 61, 65));
+        }
+
+        #[test]
+        fn rgb_fn() {
+            let h: HexColor = "rgb(177,61, 65)".parse().unwrap();
+            assert_snapshot!(h, @"#b13d41");
+        }
+
+        #[test]
+        fn css_name() {
+            let h: HexColor = "RebeccaPurple".parse().unwrap();
+            assert_snapshot!(h, @"#663399");
+        }
+
+        #[test]
+        fn slack_color_components() {
+            let h: HexColor = SlackColor::Danger.into();
+            assert_eq!((h.red(), h.green(), h.blue()), (0xa3, 0x02, 0x00));
+        }
+
+        #[test]
+        fn lighten_darken() {
+            let h = HexColor::from_rgb(100, 50, 0);
+            assert_snapshot!(h.lighten(0.5), @"#b29980");
+            assert_snapshot!(h.darken(0.5), @"#321900");
+        }
+
+        #[test]
+        fn interpolate() {
+            let from = HexColor::from_rgb(0, 0, 0);
+            let to = HexColor::from_rgb(255, 100, 10);
+            assert_snapshot!(from.interpolate(&to, 0.5), @"#803205");
+            assert_eq!(from.interpolate(&to, 2.0), to);
+        }
+
+        #[test]
+        fn severity() {
+            assert_snapshot!(HexColor::severity(0.0), @"#2eb886");
+            assert_snapshot!(HexColor::severity(0.5), @"#daa038");
+            assert_snapshot!(HexColor::severity(1.0), @"#a30200");
+        }
     }
 }
@@ -24,20 +24,78 @@ pub struct ReadmeDoctests;
 
 // TODO(cosmic): We probably want _some_ level of nesting instead of having everything in the root
 pub use crate::attachment::{Action, Attachment, AttachmentBuilder, Field, Section};
+pub use crate::block::{
+    Block, BlockElement, BlockMigration, ButtonStyle, ContextElement, FidelityLoss, TextObject,
+};
+#[cfg(feature = "client")]
+pub use crate::client::Slack;
+pub use crate::dialect::Dialect;
 pub use crate::error::{Error, Result};
 pub use crate::hex::{HexColor, SlackColor};
-pub use crate::payload::{Parse, Payload, PayloadBuilder};
-pub use crate::slack::{Slack, SlackLink, SlackText, SlackTextContent, SlackTime, SlackUserLink};
+pub use crate::payload::{MessageMetadata, Parse, Payload, PayloadBuilder};
+pub use crate::slack::{
+    SlackChannelLink, SlackLink, SlackText, SlackTextContent, SlackTime, SlackUserLink,
+};
+pub use crate::webhook::{IntoWebhookUrl, WebhookUrl};
+#[cfg(feature = "client")]
+pub use crate::workflow::WorkflowTrigger;
 
 #[macro_use]
 mod macros;
 
+#[cfg(feature = "alertmanager")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alertmanager")))]
+pub mod alertmanager;
+#[cfg(any(feature = "socket-mode", feature = "web-api"))]
+mod api;
 mod attachment;
+#[cfg(any(
+    feature = "dedup",
+    feature = "digest",
+    feature = "scheduler",
+    all(feature = "outbox", feature = "client")
+))]
+mod background;
+mod block;
 /// A blocking slack client
-#[cfg(feature = "blocking")]
-#[cfg_attr(docsrs, doc(cfg(feature = "blocking")))]
+#[cfg(any(feature = "blocking", feature = "ureq"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "blocking", feature = "ureq"))))]
 pub mod blocking;
+#[cfg(any(feature = "alertmanager", feature = "scheduler"))]
+mod civil;
+#[cfg(feature = "client")]
+mod client;
+#[cfg(feature = "config")]
+#[cfg_attr(docsrs, doc(cfg(feature = "config")))]
+pub mod config;
+#[cfg(feature = "dedup")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dedup")))]
+pub mod dedup;
+mod dialect;
+#[cfg(feature = "digest")]
+#[cfg_attr(docsrs, doc(cfg(feature = "digest")))]
+pub mod digest;
 mod error;
 mod hex;
+#[cfg(feature = "outbox")]
+#[cfg_attr(docsrs, doc(cfg(feature = "outbox")))]
+pub mod outbox;
 mod payload;
+#[cfg(feature = "scheduler")]
+#[cfg_attr(docsrs, doc(cfg(feature = "scheduler")))]
+pub mod scheduler;
 mod slack;
+#[cfg(feature = "socket-mode")]
+#[cfg_attr(docsrs, doc(cfg(feature = "socket-mode")))]
+pub mod socket_mode;
+#[cfg(feature = "teams")]
+#[cfg_attr(docsrs, doc(cfg(feature = "teams")))]
+pub mod teams;
+#[cfg(all(test, any(feature = "client", feature = "ureq")))]
+mod test_util;
+#[cfg(feature = "web-api")]
+#[cfg_attr(docsrs, doc(cfg(feature = "web-api")))]
+pub mod web_api;
+mod webhook;
+#[cfg(feature = "client")]
+mod workflow;
@@ -5,10 +5,10 @@ macro_rules! url_builder_fn {
         $name:ident, $builder:ident
     } => {
         $(#[$meta])+
-        pub fn $name<U: ::reqwest::IntoUrl>(self, $name: U) -> $builder {
+        pub fn $name<U: AsRef<str>>(self, $name: U) -> $builder {
             match self.inner {
                 Ok(mut inner) => {
-                    match $name.into_url() {
+                    match ::url::Url::parse($name.as_ref()) {
                         Ok(url) => {
                             inner.$name = Some(url);
                             $builder { inner: Ok(inner) }
@@ -0,0 +1,591 @@
+//! Keep payloads on disk until they are delivered
+//!
+//! A [`Journal`] is a file of payloads waiting to be sent, one JSON record per line. Entries are
+//! appended and synced to disk before any attempt to send them, delivered oldest first, and only
+//! marked removed once slack accepts them, so they survive outages and restarts. A payload slack
+//! rejects outright is set aside with [`Journal::reject()`] instead, so it doesn't hold back the
+//! rest.
+//!
+//! [`Outbox`] sends through a journal with the async client, retrying in the background. With
+//! the blocking client, or to replay a journal by hand, use [`Journal::replay()`]:
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "blocking")]
+//! # fn run() -> slack_hook::Result<()> {
+//! use slack_hook::blocking::Slack;
+//! use slack_hook::outbox::Journal;
+//!
+//! let slack = Slack::from_env("SLACK_WEBHOOK_URL")?;
+//! let journal = Journal::open("/var/lib/audit/outbox.jsonl")?;
+//! let delivered = journal.replay(|payload| slack.send(payload))?;
+//! println!("delivered {delivered}, {} still waiting", journal.entries()?.len());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A journal is meant to have a single writer. Replaying or purging it from another process, e.g.
+//! with `slack-hook outbox`, while a service is still sending through it can lose entries.
+
+use crate::{Error, Payload, Result, SlackTime};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+#[cfg(feature = "client")]
+mod sender;
+
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+pub use self::sender::Outbox;
+
+/// The default [`Journal::max_bytes()`], 16 MiB
+const DEFAULT_MAX_BYTES: u64 = 16 * 1024 * 1024;
+
+/// How many lines of removed entries a journal holds before it is compacted, as long as they
+/// also outnumber the waiting entries
+const COMPACT_AFTER: usize = 64;
+
+/// A payload waiting in a [`Journal`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    /// Identifies the entry within its journal, increasing in the order entries were added
+    pub id: u64,
+    /// When the entry was added
+    pub queued_at: SlackTime,
+    /// The payload to send
+    pub payload: Payload,
+    /// Why slack rejected the payload, for an entry set aside with [`Journal::reject()`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejected: Option<String>,
+}
+
+/// An append-only file of payloads waiting to be sent
+///
+/// Removing an entry appends a record of its removal, and the file is rewritten without them once
+/// they make up most of it. The entries are also kept in memory, so reading them doesn't touch
+/// the file.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    max_bytes: u64,
+    state: Mutex<State>,
+}
+
+/// What the journal file holds
+#[derive(Debug)]
+struct State {
+    entries: VecDeque<Entry>,
+    /// Entries set aside after slack rejected them, oldest first
+    rejected: Vec<Entry>,
+    /// The id of the next entry, which only counts up so that ids are never reused
+    next_id: u64,
+    /// Lines in the file that are removed entries or the records of their removal
+    dead_lines: usize,
+    /// The length of the file in bytes
+    len: u64,
+}
+
+/// A line of the journal file
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Record {
+    Entry(Box<Entry>),
+    Removed(Removed),
+    Rejected(Rejected),
+    Header(Header),
+}
+
+/// Records that the entry with this id was delivered or purged
+#[derive(Serialize, Deserialize)]
+struct Removed {
+    removed: u64,
+}
+
+/// Records that the entry with this id was set aside, and why
+#[derive(Serialize, Deserialize)]
+struct Rejected {
+    rejected: u64,
+    reason: String,
+}
+
+/// Starts a compacted journal, so its ids carry on from before
+#[derive(Serialize, Deserialize)]
+struct Header {
+    next_id: u64,
+}
+
+impl Journal {
+    /// Open the journal at `path`, creating it if it doesn't exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Journal> {
+        let path = path.as_ref().to_owned();
+        let _ = OpenOptions::new().create(true).append(true).open(&path)?;
+        let contents = fs::read_to_string(&path)?;
+        let journal = Journal {
+            state: Mutex::new(load(&path, &contents)?),
+            path,
+            max_bytes: DEFAULT_MAX_BYTES,
+        };
+
+        // a crash mid-write leaves a partial last line, which the next one would run into
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            journal.compact(&mut journal.state())?;
+        }
+        Ok(journal)
+    }
+
+    /// Refuse new entries once the journal would grow past `max_bytes`, 16 MiB by default
+    pub fn max_bytes(mut self, max_bytes: u64) -> Journal {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// The path of the journal file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add a payload to the end of the journal, returning its id
+    ///
+    /// Fails with [`Error::Outbox`] rather than drop anything when the journal is full.
+    pub fn push(&self, payload: &Payload) -> Result<u64> {
+        self.append(payload.clone())
+    }
+
+    fn append(&self, payload: Payload) -> Result<u64> {
+        let mut state = self.state();
+        let entry = Entry {
+            id: state.next_id,
+            queued_at: SlackTime::now(),
+            payload,
+            rejected: None,
+        };
+        let line = to_line(&entry)?;
+
+        let fits = |state: &State| state.len + line.len() as u64 <= self.max_bytes;
+        if !fits(&state) && state.dead_lines > 0 {
+            self.compact(&mut state)?;
+        }
+        if !fits(&state) {
+            return Err(Error::Outbox(format!(
+                "journal `{}` is full, {} of {} bytes used",
+                self.path.display(),
+                state.len,
+                self.max_bytes
+            )));
+        }
+
+        self.write_line(&mut state, &line)?;
+        state.next_id += 1;
+        let id = entry.id;
+        state.entries.push_back(entry);
+        Ok(id)
+    }
+
+    /// The entries waiting to be sent, oldest first
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        Ok(self.state().entries.iter().cloned().collect())
+    }
+
+    /// The oldest entry, which is the next to be sent
+    pub fn first(&self) -> Result<Option<Entry>> {
+        Ok(self.state().entries.front().cloned())
+    }
+
+    /// The entries set aside by [`Journal::reject()`], oldest first
+    pub fn rejected(&self) -> Result<Vec<Entry>> {
+        Ok(self.state().rejected.clone())
+    }
+
+    /// Remove the entry with `id`, whether waiting or rejected, returning whether it was there
+    pub fn remove(&self, id: u64) -> Result<bool> {
+        let mut state = self.state();
+        if let Some(index) = state.entries.iter().position(|entry| entry.id == id) {
+            self.write_line(&mut state, &to_line(&Removed { removed: id })?)?;
+            let _ = state.entries.remove(index);
+        } else if let Some(index) = state.rejected.iter().position(|entry| entry.id == id) {
+            self.write_line(&mut state, &to_line(&Removed { removed: id })?)?;
+            let _ = state.rejected.remove(index);
+        } else {
+            return Ok(false);
+        }
+        // the entry's line along with its removal
+        state.dead_lines += 2;
+        self.compact_if_mostly_dead(&mut state)?;
+        Ok(true)
+    }
+
+    /// Set aside the waiting entry with `id` because slack rejected it, returning whether it was
+    /// waiting
+    ///
+    /// It is kept in [`Journal::rejected()`] along with `reason` until it is removed, but no
+    /// longer sent.
+    pub fn reject(&self, id: u64, reason: &str) -> Result<bool> {
+        let mut state = self.state();
+        let index = match state.entries.iter().position(|entry| entry.id == id) {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        let record = Rejected {
+            rejected: id,
+            reason: reason.to_owned(),
+        };
+        self.write_line(&mut state, &to_line(&record)?)?;
+        if let Some(mut entry) = state.entries.remove(index) {
+            entry.rejected = Some(record.reason);
+            state.rejected.push(entry);
+        }
+        // compacting writes the entry again, marked rejected
+        state.dead_lines += 2;
+        self.compact_if_mostly_dead(&mut state)?;
+        Ok(true)
+    }
+
+    /// Remove every entry without sending it, rejected ones included, returning how many there
+    /// were
+    pub fn purge(&self) -> Result<usize> {
+        let mut state = self.state();
+        state.len = self.rewrite(state.next_id, [])?;
+        state.dead_lines = 0;
+        let purged = state.entries.len() + state.rejected.len();
+        state.entries.clear();
+        state.rejected.clear();
+        Ok(purged)
+    }
+
+    /// Send the entries oldest first with `send`, removing each one as it succeeds
+    ///
+    /// An entry that fails with [`Error::Rejected`] or [`Error::Api`] won't ever be accepted, so
+    /// it is set aside with [`Journal::reject()`] and the rest are sent. Any other failure stops
+    /// the replay so the order is kept, returning its error. Otherwise returns how many entries
+    /// were delivered.
+    pub fn replay<F>(&self, mut send: F) -> Result<usize>
+    where
+        F: FnMut(&Payload) -> Result<()>,
+    {
+        let mut delivered = 0;
+        while let Some(entry) = self.first()? {
+            match send(&entry.payload) {
+                Ok(()) => {
+                    let _ = self.remove(entry.id)?;
+                    delivered += 1;
+                }
+                Err(err) if is_rejection(&err) => {
+                    let _ = self.reject(entry.id, &err.to_string())?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(delivered)
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Append a line to the file and sync it to disk
+    fn write_line(&self, state: &mut State, line: &str) -> Result<()> {
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        state.len += line.len() as u64;
+        Ok(())
+    }
+
+    fn compact_if_mostly_dead(&self, state: &mut State) -> Result<()> {
+        let live = state.entries.len() + state.rejected.len();
+        if state.dead_lines >= COMPACT_AFTER && state.dead_lines > live {
+            self.compact(state)?;
+        }
+        Ok(())
+    }
+
+    /// Rewrite the file with only the rejected and waiting entries
+    fn compact(&self, state: &mut State) -> Result<()> {
+        state.len = self.rewrite(state.next_id, state.rejected.iter().chain(&state.entries))?;
+        state.dead_lines = 0;
+        Ok(())
+    }
+
+    /// Replace the file with `entries`, through a temporary file so a crash leaves either the old
+    /// journal or the new one, returning its length
+    fn rewrite<'a, I>(&self, next_id: u64, entries: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = &'a Entry>,
+    {
+        let mut contents = to_line(&Header { next_id })?;
+        for entry in entries {
+            contents.push_str(&to_line(entry)?);
+        }
+
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let mut temp = File::create(&temp_path)?;
+        temp.write_all(contents.as_bytes())?;
+        temp.sync_all()?;
+        fs::rename(&temp_path, &self.path)?;
+        Ok(contents.len() as u64)
+    }
+}
+
+/// A record as a line of the journal file
+fn to_line<T: Serialize>(record: &T) -> Result<String> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    Ok(line)
+}
+
+/// Whether sending a payload failed in a way that sending it again won't fix
+fn is_rejection(err: &Error) -> bool {
+    matches!(err, Error::Rejected(_) | Error::Api(_))
+}
+
+/// Replay the journal's lines, skipping a partial last line left by a crash mid-write
+fn load(path: &Path, contents: &str) -> Result<State> {
+    let mut state = State {
+        entries: VecDeque::new(),
+        rejected: Vec::new(),
+        next_id: 1,
+        dead_lines: 0,
+        len: contents.len() as u64,
+    };
+    let mut lines = contents.split('\n').enumerate().peekable();
+    while let Some((i, line)) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(Record::Entry(entry)) => {
+                state.next_id = state.next_id.max(entry.id + 1);
+                if entry.rejected.is_some() {
+                    state.rejected.push(*entry);
+                } else {
+                    state.entries.push_back(*entry);
+                }
+            }
+            Ok(Record::Removed(Removed { removed })) => {
+                state.next_id = state.next_id.max(removed + 1);
+                // removals are nearly always of the oldest entry
+                if let Some(index) = state.entries.iter().position(|entry| entry.id == removed) {
+                    let _ = state.entries.remove(index);
+                    state.dead_lines += 1;
+                } else if let Some(index) = state.rejected.iter().position(|e| e.id == removed) {
+                    let _ = state.rejected.remove(index);
+                    state.dead_lines += 1;
+                }
+                state.dead_lines += 1;
+            }
+            Ok(Record::Rejected(Rejected { rejected, reason })) => {
+                state.next_id = state.next_id.max(rejected + 1);
+                if let Some(index) = state.entries.iter().position(|entry| entry.id == rejected) {
+                    if let Some(mut entry) = state.entries.remove(index) {
+                        entry.rejected = Some(reason);
+                        state.rejected.push(entry);
+                    }
+                    state.dead_lines += 1;
+                }
+                state.dead_lines += 1;
+            }
+            Ok(Record::Header(Header { next_id })) => state.next_id = state.next_id.max(next_id),
+            Err(_) if lines.peek().is_none() => {}
+            Err(err) => {
+                return Err(Error::Outbox(format!(
+                    "corrupt entry on line {} of `{}`: {err}",
+                    i + 1,
+                    path.display()
+                )))
+            }
+        }
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PayloadBuilder;
+    use insta::assert_snapshot;
+    use std::env;
+
+    /// A fresh journal in the temp dir, named after the test using it
+    pub(super) fn journal(name: &str) -> Journal {
+        let path = env::temp_dir().join(format!(
+            "slack-hook-outbox-{}-{name}.jsonl",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        Journal::open(path).unwrap()
+    }
+
+    fn payload(text: &str) -> Payload {
+        PayloadBuilder::new().text(text).build().unwrap()
+    }
+
+    fn texts(journal: &Journal) -> Vec<String> {
+        journal
+            .entries()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.payload.text.as_ref().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn survives_reopening() {
+        let journal = journal("reopen");
+        assert_eq!(journal.push(&payload("one")).unwrap(), 1);
+        assert_eq!(journal.push(&payload("two")).unwrap(), 2);
+        assert!(journal.remove(1).unwrap());
+        assert!(!journal.remove(1).unwrap());
+
+        let reopened = Journal::open(journal.path()).unwrap();
+        assert_eq!(texts(&reopened), ["two"]);
+        assert_eq!(reopened.push(&payload("three")).unwrap(), 3);
+        assert_eq!(reopened.purge().unwrap(), 2);
+        assert!(texts(&reopened).is_empty());
+        assert!(texts(&Journal::open(journal.path()).unwrap()).is_empty());
+        fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn ids_are_never_reused() {
+        let journal = journal("ids");
+        for text in ["one", "two"] {
+            let _ = journal.push(&payload(text)).unwrap();
+        }
+        assert!(journal.remove(2).unwrap());
+        assert_eq!(journal.purge().unwrap(), 1);
+        assert_eq!(journal.push(&payload("three")).unwrap(), 3);
+        assert_eq!(journal.replay(|_| Ok(())).unwrap(), 1);
+
+        let reopened = Journal::open(journal.path()).unwrap();
+        assert_eq!(reopened.push(&payload("four")).unwrap(), 4);
+        fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn compacts_removed_entries() {
+        let journal = journal("compact");
+        for i in 0..200 {
+            let id = journal.push(&payload(&format!("entry {i}"))).unwrap();
+            if i % 10 != 0 {
+                assert!(journal.remove(id).unwrap());
+            }
+        }
+        let lines = fs::read_to_string(journal.path()).unwrap().lines().count();
+        assert!(lines < 20 + COMPACT_AFTER, "{}", lines);
+
+        let reopened = Journal::open(journal.path()).unwrap();
+        assert_eq!(texts(&reopened), texts(&journal));
+        assert_eq!(texts(&reopened).len(), 20);
+        assert_eq!(reopened.push(&payload("last")).unwrap(), 201);
+        fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn replay_stops_at_failure() {
+        let journal = journal("replay");
+        for text in ["one", "two", "three"] {
+            let _ = journal.push(&payload(text)).unwrap();
+        }
+
+        let mut sent = Vec::new();
+        let err = journal
+            .replay(|payload| {
+                let text = payload.text.as_ref().unwrap().to_string();
+                if text == "two" {
+                    return Err(Error::Slack(
+                        "HTTP error 503 Service Unavailable".to_owned(),
+                    ));
+                }
+                sent.push(text);
+                Ok(())
+            })
+            .unwrap_err();
+        assert_snapshot!(err, @"slack service error: HTTP error 503 Service Unavailable");
+        assert_eq!(sent, ["one"]);
+        assert_eq!(texts(&journal), ["two", "three"]);
+
+        assert_eq!(journal.replay(|_| Ok(())).unwrap(), 2);
+        assert!(texts(&journal).is_empty());
+        fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn sets_aside_rejected() {
+        let journal = journal("rejected");
+        for text in ["one", "two", "three"] {
+            let _ = journal.push(&payload(text)).unwrap();
+        }
+
+        let delivered = journal
+            .replay(
+                |payload| match payload.text.as_ref().unwrap().to_string().as_str() {
+                    "two" => Err(Error::Rejected(
+                        "HTTP error 400 Bad Request: invalid_payload".to_owned(),
+                    )),
+                    _ => Ok(()),
+                },
+            )
+            .unwrap();
+        assert_eq!(delivered, 2);
+        assert!(texts(&journal).is_empty());
+
+        let reopened = Journal::open(journal.path()).unwrap();
+        let rejected = reopened.rejected().unwrap();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].id, 2);
+        assert_snapshot!(
+            rejected[0].rejected.as_ref().unwrap(),
+            @"slack rejected the payload: HTTP error 400 Bad Request: invalid_payload"
+        );
+        assert!(!reopened.reject(2, "again").unwrap());
+        assert!(reopened.remove(2).unwrap());
+        assert!(Journal::open(journal.path())
+            .unwrap()
+            .rejected()
+            .unwrap()
+            .is_empty());
+        fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn skips_partial_write() {
+        let journal = journal("partial");
+        let _ = journal.push(&payload("one")).unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(journal.path())
+            .unwrap();
+        file.write_all(br#"{"id":2,"queued_at":1714564800,"payl"#)
+            .unwrap();
+
+        let reopened = Journal::open(journal.path()).unwrap();
+        assert_eq!(texts(&reopened), ["one"]);
+        assert_eq!(reopened.push(&payload("two")).unwrap(), 2);
+        assert_eq!(texts(&reopened), ["one", "two"]);
+        let reopened = Journal::open(journal.path()).unwrap();
+        assert_eq!(texts(&reopened), ["one", "two"]);
+
+        fs::write(journal.path(), "not json\n{}\n").unwrap();
+        let err = Journal::open(journal.path()).unwrap_err().to_string();
+        assert!(err.starts_with("outbox error: corrupt entry on line 1 of"));
+        fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn refuses_when_full() {
+        let journal = journal("full").max_bytes(100);
+        let _ = journal.push(&payload("one")).unwrap();
+        let err = journal.push(&payload("two")).unwrap_err().to_string();
+        assert!(err.contains("is full"), "{}", err);
+        assert_eq!(texts(&journal), ["one"]);
+        fs::remove_file(journal.path()).unwrap();
+    }
+}
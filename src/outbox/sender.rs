@@ -0,0 +1,269 @@
+use super::{is_rejection, Journal};
+use crate::background::{ignore_errors, ErrorHandler};
+use crate::{Error, Payload, Result, Slack};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::Mutex;
+
+/// The longest wait between retries
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Sends payloads with [`Slack::send()`] through a [`Journal`], so none are lost when slack
+/// can't be reached
+///
+/// Payloads are delivered in the order they were sent. A failed delivery is retried by a
+/// background task, which needs a Tokio runtime and doubles its wait after each failure. A
+/// payload slack rejects outright, e.g. as `invalid_payload`, isn't retried but set aside in
+/// [`Journal::rejected()`], so it doesn't hold back the rest.
+///
+/// Entries left from a previous run are delivered with the next send, or right away with
+/// [`Outbox::deliver()`].
+///
+/// ```rust,no_run
+/// # async fn run() -> slack_hook::Result<()> {
+/// use slack_hook::outbox::{Journal, Outbox};
+/// use slack_hook::{PayloadBuilder, Slack};
+///
+/// let outbox = Outbox::new(
+///     Slack::from_env("SLACK_WEBHOOK_URL")?,
+///     Journal::open("/var/lib/audit/outbox.jsonl")?,
+/// )
+/// .on_error(|err| eprintln!("audit notification delayed: {err}"));
+/// // anything left over from before a restart
+/// outbox.deliver().await?;
+///
+/// let payload = PayloadBuilder::new().text("alice granted admin to bob").build()?;
+/// outbox.send(&payload).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Outbox {
+    slack: Arc<Slack>,
+    journal: Arc<Journal>,
+    retry_delay: Duration,
+    /// Held while delivering, so two deliveries can't send the same entry
+    delivering: Arc<Mutex<()>>,
+    retrying: Arc<AtomicBool>,
+    on_error: ErrorHandler,
+}
+
+impl Outbox {
+    /// Construct an outbox that sends with `slack` through `journal`
+    pub fn new(slack: Slack, journal: Journal) -> Outbox {
+        Outbox {
+            slack: Arc::new(slack),
+            journal: Arc::new(journal),
+            retry_delay: Duration::from_secs(1),
+            delivering: Arc::default(),
+            retrying: Arc::default(),
+            on_error: ignore_errors(),
+        }
+    }
+
+    /// Set how long to wait before the first retry, 1 second by default
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Outbox {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Have `on_error` see each failed delivery. The payload stays in the journal and is retried
+    /// regardless, unless slack rejected it and it was set aside
+    pub fn on_error<F>(mut self, on_error: F) -> Outbox
+    where
+        F: Fn(Error) + Send + Sync + 'static,
+    {
+        self.on_error = Arc::new(on_error);
+        self
+    }
+
+    /// The journal payloads wait in
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    /// Add a payload to the journal and deliver it along with any before it
+    ///
+    /// Only fails when the payload can't be journaled. Once it is, a failed delivery is
+    /// passed to [`on_error`](Outbox::on_error) and retried in the background.
+    pub async fn send(&self, payload: &Payload) -> Result<()> {
+        let payload = payload.clone();
+        let _ = self
+            .blocking(move |journal| journal.append(payload))
+            .await?;
+        if let Err(err) = self.deliver().await {
+            (self.on_error)(err);
+            self.retry();
+        }
+        Ok(())
+    }
+
+    /// Deliver the journal's entries oldest first, returning how many were delivered
+    ///
+    /// Entries slack rejects are set aside with [`Journal::reject()`] and their errors passed to
+    /// [`on_error`](Outbox::on_error). Stops at any other failure and returns its error, leaving it
+    /// and everything after it in the journal.
+    pub async fn deliver(&self) -> Result<usize> {
+        let _delivering = self.delivering.lock().await;
+        let mut delivered = 0;
+        while let Some(entry) = self.journal.first()? {
+            let id = entry.id;
+            match self.slack.send(&entry.payload).await {
+                Ok(()) => {
+                    let _ = self.blocking(move |journal| journal.remove(id)).await?;
+                    delivered += 1;
+                }
+                Err(err) if is_rejection(&err) => {
+                    let reason = err.to_string();
+                    let _ = self
+                        .blocking(move |journal| journal.reject(id, &reason))
+                        .await?;
+                    (self.on_error)(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Run `f` on the journal from the blocking thread pool, since it writes and syncs the file
+    async fn blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Journal) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let journal = Arc::clone(&self.journal);
+        match tokio::task::spawn_blocking(move || f(&journal)).await {
+            Ok(result) => result,
+            Err(err) => match err.try_into_panic() {
+                Ok(panic) => std::panic::resume_unwind(panic),
+                Err(err) => Err(Error::Outbox(err.to_string())),
+            },
+        }
+    }
+
+    /// Keep delivering in the background until the journal is empty, unless that's already
+    /// happening
+    fn retry(&self) {
+        if self.retrying.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let outbox = self.clone();
+        let _retry = tokio::spawn(async move {
+            let mut delay = outbox.retry_delay;
+            loop {
+                tokio::time::sleep(delay).await;
+                if let Err(err) = outbox.deliver().await {
+                    (outbox.on_error)(err);
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                    continue;
+                }
+
+                outbox.retrying.store(false, Ordering::SeqCst);
+                // a send that failed before the flag was cleared left its retry to this task
+                let waiting = matches!(outbox.journal.first(), Ok(Some(_)));
+                if !waiting || outbox.retrying.swap(true, Ordering::SeqCst) {
+                    break;
+                }
+                delay = outbox.retry_delay;
+            }
+        });
+    }
+}
+
+impl fmt::Debug for Outbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Outbox")
+            .field("slack", &self.slack)
+            .field("journal", &self.journal)
+            .field("retry_delay", &self.retry_delay)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{hook_url, received, respond_with, wait_for};
+    use crate::{outbox::test::journal, PayloadBuilder};
+    use std::{fs, sync::atomic::AtomicUsize};
+    use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn retries_in_order() {
+        let server = MockServer::start().await;
+        let down = Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount_as_scoped(&server)
+            .await;
+
+        let errors = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&errors);
+        let outbox = Outbox::new(Slack::new(hook_url(&server)).unwrap(), journal("retries"))
+            .retry_delay(Duration::from_millis(100))
+            .on_error(move |_| {
+                let _ = counted.fetch_add(1, Ordering::SeqCst);
+            });
+
+        for text in ["first", "second"] {
+            let payload = PayloadBuilder::new().text(text).build().unwrap();
+            outbox.send(&payload).await.unwrap();
+        }
+        assert_eq!(outbox.journal().entries().unwrap().len(), 2);
+        assert!(errors.load(Ordering::SeqCst) >= 2);
+
+        drop(down);
+        respond_with(&server, 200).await;
+        wait_for(|| outbox.journal().entries().unwrap().is_empty()).await;
+
+        let bodies = received(&server, 2).await;
+        // earlier attempts at `first` failed, and `second` waited until it was delivered
+        let [.., first, second] = bodies.as_slice() else {
+            panic!("expected at least two requests, found {}", bodies.len());
+        };
+        assert_eq!(first["text"], "first");
+        assert_eq!(second["text"], "second");
+        fs::remove_file(outbox.journal().path()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sets_aside_rejected() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("invalid_payload"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        respond_with(&server, 200).await;
+
+        let errors = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&errors);
+        let outbox = Outbox::new(Slack::new(hook_url(&server)).unwrap(), journal("rejects"))
+            .on_error(move |_| {
+                let _ = counted.fetch_add(1, Ordering::SeqCst);
+            });
+
+        for text in ["bad", "good"] {
+            let payload = PayloadBuilder::new().text(text).build().unwrap();
+            outbox.send(&payload).await.unwrap();
+        }
+        assert!(outbox.journal().entries().unwrap().is_empty());
+        let rejected = outbox.journal().rejected().unwrap();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(
+            rejected[0].payload.text.as_ref().unwrap().to_string(),
+            "bad"
+        );
+        assert_eq!(errors.load(Ordering::SeqCst), 1);
+        // nothing was left to retry
+        assert!(!outbox.retrying.load(Ordering::SeqCst));
+        assert_eq!(received(&server, 2).await.len(), 2);
+        fs::remove_file(outbox.journal().path()).unwrap();
+    }
+}
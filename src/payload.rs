@@ -1,11 +1,12 @@
-use crate::{Attachment, Result, SlackText};
-use reqwest::Url;
-use serde::{Serialize, Serializer};
+use crate::{Attachment, Block, Result, SlackText};
+use serde::{de::DeserializeOwned, Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use url::Url;
 
 /// Payload to send to slack
 /// <https://api.slack.com/incoming-webhooks>
 /// <https://api.slack.com/methods/chat.postMessage>
-#[derive(Serialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Payload {
     /// text to send
     /// despite `text` stated as required, it does not seem to be
@@ -29,6 +30,14 @@ pub struct Payload {
     /// attachments to send
     #[serde(skip_serializing_if = "Option::is_none")]
     pub attachments: Option<Vec<Attachment>>,
+    /// Block Kit blocks to lay out the message with, in which case `text` becomes the
+    /// notification fallback
+    /// <https://api.slack.com/block-kit>
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<Vec<Block>>,
+    /// structured data for other apps to consume, see [`MessageMetadata`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<MessageMetadata>,
     /// whether slack will try to fetch links and create an attachment
     /// <https://api.slack.com/docs/unfurling>
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -44,8 +53,109 @@ pub struct Payload {
     pub parse: Option<Parse>,
 }
 
+impl Payload {
+    /// Merge a template of `defaults` into this payload, with this payload's values winning
+    ///
+    /// Each field that is unset here is taken from `defaults`, with one exception: `icon_url` and
+    /// `icon_emoji` are treated as a single setting. A payload that sets either one keeps its own
+    /// icon, and otherwise both are taken from `defaults`.
+    pub fn with_defaults(&self, defaults: &Payload) -> Payload {
+        fn or<T: Clone>(value: &Option<T>, default: &Option<T>) -> Option<T> {
+            value.as_ref().or(default.as_ref()).cloned()
+        }
+
+        let (icon_url, icon_emoji) = if self.icon_url.is_some() || self.icon_emoji.is_some() {
+            (self.icon_url.clone(), self.icon_emoji.clone())
+        } else {
+            (defaults.icon_url.clone(), defaults.icon_emoji.clone())
+        };
+
+        Payload {
+            text: or(&self.text, &defaults.text),
+            channel: or(&self.channel, &defaults.channel),
+            username: or(&self.username, &defaults.username),
+            icon_url,
+            icon_emoji,
+            attachments: or(&self.attachments, &defaults.attachments),
+            blocks: or(&self.blocks, &defaults.blocks),
+            metadata: or(&self.metadata, &defaults.metadata),
+            unfurl_links: or(&self.unfurl_links, &defaults.unfurl_links),
+            unfurl_media: or(&self.unfurl_media, &defaults.unfurl_media),
+            link_names: or(&self.link_names, &defaults.link_names),
+            parse: or(&self.parse, &defaults.parse),
+        }
+    }
+}
+
+/// Structured data attached to a message for other apps to consume
+/// <https://api.slack.com/metadata>
+///
+/// The `event_payload` can be any serializable type, and is held as a JSON [`Value`] on a
+/// [`Payload`]. Metadata read back from slack, e.g. with
+/// [`WebApi::message_metadata()`](crate::web_api::WebApi::message_metadata), can be turned back
+/// into a typed payload with [`MessageMetadata::parse()`].
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use slack_hook::{MessageMetadata, PayloadBuilder};
+///
+/// #[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// struct Build {
+///     version: String,
+///     commit: String,
+/// }
+///
+/// let build = Build { version: "1.2.3".into(), commit: "a1b2c3d".into() };
+/// let payload = PayloadBuilder::new()
+///     .text("Deployed 1.2.3")
+///     .metadata(MessageMetadata::new("deploy_finished", &build))
+///     .build()
+///     .unwrap();
+///
+/// let metadata = payload.metadata.unwrap().parse::<Build>().unwrap();
+/// assert_eq!(metadata.event_payload, build);
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MessageMetadata<T = Value> {
+    /// Name of the event, e.g. `deploy_finished`
+    pub event_type: String,
+    /// The event's data
+    pub event_payload: T,
+}
+
+impl<T> MessageMetadata<T> {
+    /// Construct metadata for an event
+    pub fn new<S: Into<String>>(event_type: S, event_payload: T) -> MessageMetadata<T> {
+        MessageMetadata {
+            event_type: event_type.into(),
+            event_payload,
+        }
+    }
+}
+
+impl<T: Serialize> MessageMetadata<T> {
+    /// Convert the `event_payload` into JSON, as held by a [`Payload`]
+    pub fn to_value(&self) -> Result<MessageMetadata> {
+        Ok(MessageMetadata {
+            event_type: self.event_type.clone(),
+            event_payload: serde_json::to_value(&self.event_payload)?,
+        })
+    }
+}
+
+impl MessageMetadata {
+    /// Deserialize the `event_payload` into `T`
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<MessageMetadata<T>> {
+        Ok(MessageMetadata {
+            event_type: self.event_type.clone(),
+            event_payload: T::deserialize(&self.event_payload)?,
+        })
+    }
+}
+
 /// Change how messages are treated.
-#[derive(Debug)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
 pub enum Parse {
     /// Full
     Full,
@@ -131,6 +241,28 @@ impl PayloadBuilder {
         self
     }
 
+    /// Set the blocks
+    pub fn blocks(mut self, blocks: Vec<Block>) -> Self {
+        if let Ok(inner) = &mut self.inner {
+            inner.blocks = Some(blocks);
+        }
+        self
+    }
+
+    /// Set the metadata, failing the build if its `event_payload` can't be serialized
+    pub fn metadata<T: Serialize>(self, metadata: MessageMetadata<T>) -> Self {
+        match self.inner {
+            Ok(mut inner) => match metadata.to_value() {
+                Ok(metadata) => {
+                    inner.metadata = Some(metadata);
+                    PayloadBuilder { inner: Ok(inner) }
+                }
+                Err(err) => PayloadBuilder { inner: Err(err) },
+            },
+            _ => self,
+        }
+    }
+
     /// whether slack will try to fetch links and create an attachment
     /// <https://api.slack.com/docs/unfurling>
     pub fn unfurl_links(mut self, b: bool) -> Self {
@@ -171,3 +303,141 @@ impl PayloadBuilder {
         self.inner
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{MessageMetadata, PayloadBuilder};
+    use insta::{assert_json_snapshot, assert_snapshot};
+
+    fn defaults() -> crate::Payload {
+        PayloadBuilder::new()
+            .channel("#alerts")
+            .username("Alert Bot")
+            .icon_emoji(":robot_face:")
+            .unfurl_links(false)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn defaults_fill_unset_fields() {
+        let p = PayloadBuilder::new().text("disk full").build().unwrap();
+        assert_json_snapshot!(p.with_defaults(&defaults()), @r###"
+        {
+          "text": "disk full",
+          "channel": "#alerts",
+          "username": "Alert Bot",
+          "icon_emoji": ":robot_face:",
+          "unfurl_links": false
+        }
+        "###);
+    }
+
+    #[test]
+    fn message_values_win() {
+        let p = PayloadBuilder::new()
+            .text("deployed")
+            .channel("#deploys")
+            .unfurl_links(true)
+            .build()
+            .unwrap();
+        assert_json_snapshot!(p.with_defaults(&defaults()), @r###"
+        {
+          "text": "deployed",
+          "channel": "#deploys",
+          "username": "Alert Bot",
+          "icon_emoji": ":robot_face:",
+          "unfurl_links": true
+        }
+        "###);
+    }
+
+    #[test]
+    fn deserialize_keeps_text_escaped() {
+        let p: crate::Payload = serde_json::from_str(
+            r##"{
+                "text": "a &lt;b&gt;",
+                "attachments": [{ "fallback": "f", "color": "#ABC", "ts": 123456789 }],
+                "link_names": 1,
+                "parse": "full"
+            }"##,
+        )
+        .unwrap();
+        assert_json_snapshot!(p, @r###"
+        {
+          "text": "a &lt;b&gt;",
+          "attachments": [
+            {
+              "fallback": "f",
+              "color": "#aabbcc",
+              "ts": 123456789
+            }
+          ],
+          "link_names": 1,
+          "parse": "full"
+        }
+        "###);
+    }
+
+    #[test]
+    fn metadata() {
+        #[derive(serde::Serialize)]
+        struct Build {
+            version: &'static str,
+            commit: &'static str,
+        }
+
+        let p = PayloadBuilder::new()
+            .text("Deployed 1.2.3")
+            .metadata(MessageMetadata::new(
+                "deploy_finished",
+                Build {
+                    version: "1.2.3",
+                    commit: "a1b2c3d",
+                },
+            ))
+            .build()
+            .unwrap();
+        assert_json_snapshot!(p, @r###"
+        {
+          "text": "Deployed 1.2.3",
+          "metadata": {
+            "event_type": "deploy_finished",
+            "event_payload": {
+              "commit": "a1b2c3d",
+              "version": "1.2.3"
+            }
+          }
+        }
+        "###);
+    }
+
+    #[test]
+    fn unserializable_metadata() {
+        let mut event_payload = std::collections::HashMap::new();
+        let _ = event_payload.insert((1, 2), "tuple keys aren't valid JSON");
+        let err = PayloadBuilder::new()
+            .metadata(MessageMetadata::new("invalid", event_payload))
+            .build()
+            .unwrap_err();
+        assert_snapshot!(err, @"key must be a string");
+    }
+
+    #[test]
+    fn icon_is_a_single_setting() {
+        let p = PayloadBuilder::new()
+            .text("custom icon")
+            .icon_url("https://example.com/icon.png")
+            .build()
+            .unwrap();
+        assert_json_snapshot!(p.with_defaults(&defaults()), @r###"
+        {
+          "text": "custom icon",
+          "channel": "#alerts",
+          "username": "Alert Bot",
+          "icon_url": "https://example.com/icon.png",
+          "unfurl_links": false
+        }
+        "###);
+    }
+}
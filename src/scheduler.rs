@@ -0,0 +1,236 @@
+//! Send payloads through a webhook at a later time
+//!
+//! Incoming webhooks post immediately, so the [`Scheduler`] holds payloads in-process until they
+//! are due. Nothing is persisted: anything still scheduled when the process exits is never sent.
+//! With a Web API token, [`WebApi::schedule_message()`] has slack hold the message instead.
+//!
+//! [`WebApi::schedule_message()`]: crate::web_api::WebApi::schedule_message
+//!
+//! ```rust,no_run
+//! # async fn run() -> slack_hook::Result<()> {
+//! use slack_hook::scheduler::{Cron, Scheduler};
+//! use slack_hook::{PayloadBuilder, Slack, SlackTime};
+//! use std::time::{Duration, SystemTime};
+//!
+//! let scheduler = Scheduler::new(Slack::from_env("SLACK_WEBHOOK_URL")?)
+//!     .on_error(|err| eprintln!("scheduled send failed: {err}"));
+//!
+//! let reminder = PayloadBuilder::new().text("Deploy freeze starts now").build()?;
+//! let at = SlackTime::from(SystemTime::now() + Duration::from_secs(60 * 60));
+//! let handle = scheduler.schedule(reminder, at);
+//!
+//! let standup = PayloadBuilder::new().text("Standup in 5 minutes").build()?;
+//! let _daily = scheduler.schedule_cron(standup, "55 8 * * mon-fri".parse()?);
+//!
+//! // the freeze was called off
+//! handle.cancel();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::background::{ignore_errors, ErrorHandler};
+use crate::{Error, Payload, Slack, SlackTime};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::task::JoinHandle;
+
+mod cron;
+
+pub use self::cron::Cron;
+
+/// Holds payloads until they are due and then sends them with [`Slack::send()`]
+///
+/// Every schedule gets its own spawned task, so scheduling outside a Tokio runtime panics.
+#[derive(Clone)]
+pub struct Scheduler {
+    slack: Arc<Slack>,
+    on_error: ErrorHandler,
+}
+
+impl Scheduler {
+    /// Construct a scheduler that sends with `slack`
+    pub fn new(slack: Slack) -> Scheduler {
+        Scheduler {
+            slack: Arc::new(slack),
+            on_error: ignore_errors(),
+        }
+    }
+
+    /// Report failed sends to `on_error`, as no caller is waiting on them. They are dropped by
+    /// default
+    pub fn on_error<F>(mut self, on_error: F) -> Scheduler
+    where
+        F: Fn(Error) + Send + Sync + 'static,
+    {
+        self.on_error = Arc::new(on_error);
+        self
+    }
+
+    /// Send `payload` at `at`, or right away if that has already passed
+    pub fn schedule(&self, payload: Payload, at: SlackTime) -> ScheduleHandle {
+        let slack = Arc::clone(&self.slack);
+        let on_error = Arc::clone(&self.on_error);
+        ScheduleHandle {
+            task: tokio::spawn(async move {
+                sleep_until(at).await;
+                if let Err(err) = slack.send(&payload).await {
+                    on_error(err);
+                }
+            }),
+        }
+    }
+
+    /// Send `payload` every time `cron` matches, until cancelled
+    ///
+    /// A failed send doesn't stop later ones. If a send runs past the next match, that match is
+    /// skipped rather than sent late.
+    pub fn schedule_cron(&self, payload: Payload, cron: Cron) -> ScheduleHandle {
+        let slack = Arc::clone(&self.slack);
+        let on_error = Arc::clone(&self.on_error);
+        ScheduleHandle {
+            task: tokio::spawn(async move {
+                let mut after = SlackTime::now();
+                while let Some(next) = cron.next_after(after) {
+                    sleep_until(next).await;
+                    if let Err(err) = slack.send(&payload).await {
+                        on_error(err);
+                    }
+                    after = next.max(SlackTime::now());
+                }
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("slack", &self.slack)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Controls a payload scheduled with a [`Scheduler`]
+///
+/// Dropping the handle leaves the payload scheduled.
+#[derive(Debug)]
+pub struct ScheduleHandle {
+    task: JoinHandle<()>,
+}
+
+impl ScheduleHandle {
+    /// Stop the payload from being sent, unless it is already being sent
+    pub fn cancel(&self) {
+        self.task.abort();
+    }
+
+    /// Whether the payload was sent or cancelled, which a cron schedule only ever is by
+    /// cancelling it
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+async fn sleep_until(at: SlackTime) {
+    let wait = match at.to_system_time() {
+        Some(at) => at
+            .duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+        // too far off for `SystemTime`, which tokio treats as never
+        None if at.timestamp() > 0 => Duration::MAX,
+        None => Duration::ZERO,
+    };
+    tokio::time::sleep(wait).await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{hook_url, respond_with, wait_for};
+    use crate::PayloadBuilder;
+    use wiremock::{
+        matchers::{body_json, method},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn slack(server: &MockServer) -> Slack {
+        Slack::new(hook_url(server)).unwrap()
+    }
+
+    fn payload() -> Payload {
+        PayloadBuilder::new().text("later").build().unwrap()
+    }
+
+    fn soon(millis: u64) -> SlackTime {
+        SlackTime::from(SystemTime::now() + Duration::from_millis(millis))
+    }
+
+    #[tokio::test]
+    async fn sends_when_due() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_json(serde_json::json!({ "text": "later" })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        tokio::time::pause();
+        let handle = Scheduler::new(slack(&server)).schedule(payload(), soon(60_000));
+        assert!(!handle.is_finished());
+        wait_for(|| handle.is_finished()).await;
+    }
+
+    #[tokio::test]
+    async fn cancel() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        tokio::time::pause();
+        let handle = Scheduler::new(slack(&server)).schedule(payload(), soon(60_000));
+        handle.cancel();
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        assert!(handle.is_finished());
+    }
+
+    #[tokio::test]
+    async fn beyond_system_time() {
+        let server = MockServer::start().await;
+        let scheduler = Scheduler::new(slack(&server));
+
+        let never = scheduler.schedule(payload(), SlackTime::from_unix(i64::MAX, 0));
+        tokio::task::yield_now().await;
+        assert!(!never.is_finished());
+        never.cancel();
+    }
+
+    #[tokio::test]
+    async fn reports_errors() {
+        let server = MockServer::start().await;
+        respond_with(&server, 500).await;
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = std::sync::Mutex::new(tx);
+        let scheduler = Scheduler::new(slack(&server)).on_error(move |err| {
+            tx.lock().unwrap().send(err.to_string()).unwrap();
+        });
+        let _handle = scheduler.schedule(payload(), SlackTime::from_unix(0, 0));
+        let mut errors = Vec::new();
+        wait_for(|| {
+            errors.extend(rx.try_iter());
+            !errors.is_empty()
+        })
+        .await;
+        assert_eq!(
+            errors,
+            ["slack service error: HTTP error 500 Internal Server Error"]
+        );
+    }
+}
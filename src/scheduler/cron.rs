@@ -0,0 +1,233 @@
+use crate::civil::{civil_from_days, weekday_from_days};
+use crate::{Error, Result, SlackTime};
+use std::{fmt, str::FromStr};
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+/// How far ahead to look for a match before giving up, long enough to reach the next leap day
+const SEARCH_DAYS: i64 = 366 * 8;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A cron schedule, evaluated in UTC
+///
+/// This is the standard five field form: minute, hour, day of the month, month and day of the
+/// week. Each field is `*`, a value, a range like `1-5`, a step like `*/15` or `0-30/10`, or a
+/// comma separated list of those. Months and days of the week can be named, e.g. `jan` or `mon`,
+/// and Sunday is either `0` or `7`. The `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly`
+/// shorthands are accepted too.
+///
+/// Like cron, when both the day of the month and the day of the week are restricted, a day
+/// matching either one is used.
+///
+/// ```
+/// use slack_hook::scheduler::Cron;
+/// use slack_hook::SlackTime;
+///
+/// // 09:30 on weekdays
+/// let standup: Cron = "30 9 * * mon-fri".parse().unwrap();
+/// // Friday 2024-05-03 12:00:00 UTC
+/// let friday = SlackTime::from_unix(1_714_737_600, 0);
+/// // Monday 2024-05-06 09:30:00 UTC
+/// assert_eq!(standup.next_after(friday), Some(SlackTime::from_unix(1_714_987_800, 0)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of the month or the day of the week was left as `*`
+    any_day: bool,
+}
+
+impl Cron {
+    /// Parse a cron expression
+    pub fn new(expression: &str) -> Result<Cron> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<_> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = match fields[..] {
+            [minute, hour, day, month, weekday] => [minute, hour, day, month, weekday],
+            _ => {
+                return Err(Error::Cron(format!(
+                    "expected 5 fields, found {}: `{expression}`",
+                    fields.len()
+                )))
+            }
+        };
+
+        let weekdays = parse_field(weekday, "day of the week", 0, 7, &WEEKDAYS)?;
+        Ok(Cron {
+            expression: expression.trim().to_owned(),
+            minutes: parse_field(minute, "minute", 0, 59, &[])?,
+            hours: parse_field(hour, "hour", 0, 23, &[])?,
+            days: parse_field(day, "day of the month", 1, 31, &[])?,
+            months: parse_field(month, "month", 1, 12, &MONTHS)?,
+            // 7 is another name for Sunday
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            any_day: day.starts_with('*') || weekday.starts_with('*'),
+        })
+    }
+
+    /// The first time after `time` that matches, or `None` if nothing matches in the next eight
+    /// years, e.g. `0 0 30 2 *`
+    pub fn next_after(&self, time: SlackTime) -> Option<SlackTime> {
+        let start = time.timestamp().div_euclid(60) + 1;
+        let first_day = start.div_euclid(MINUTES_PER_DAY);
+        let mut from = start.rem_euclid(MINUTES_PER_DAY);
+
+        for day in first_day..first_day + SEARCH_DAYS {
+            if self.matches_day(day) {
+                let found = (from..MINUTES_PER_DAY).find(|minute| {
+                    contains(self.hours, minute / 60) && contains(self.minutes, minute % 60)
+                });
+                if let Some(minute) = found {
+                    return Some(SlackTime::from_unix(
+                        (day * MINUTES_PER_DAY + minute) * 60,
+                        0,
+                    ));
+                }
+            }
+            from = 0;
+        }
+        None
+    }
+
+    fn matches_day(&self, day: i64) -> bool {
+        let (_, month, day_of_month) = civil_from_days(day);
+        if !contains(self.months, month) {
+            return false;
+        }
+        let by_date = contains(self.days, day_of_month);
+        let by_weekday = contains(self.weekdays, weekday_from_days(day));
+        if self.any_day {
+            by_date && by_weekday
+        } else {
+            by_date || by_weekday
+        }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Cron> {
+        Cron::new(s)
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+fn contains(set: u64, value: i64) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parse one field into a bit set of the values it matches
+fn parse_field(field: &str, name: &str, min: u32, max: u32, names: &[&str]) -> Result<u64> {
+    let invalid = |reason: &str| Error::Cron(format!("invalid {name} `{field}`: {reason}"));
+    let value = |s: &str| -> Result<u32> {
+        let lower = s.to_ascii_lowercase();
+        let value = match names.iter().position(|name| *name == lower) {
+            Some(i) => i as u32 + min,
+            None => s.parse().map_err(|_| invalid("unrecognized value"))?,
+        };
+        if (min..=max).contains(&value) {
+            Ok(value)
+        } else {
+            Err(invalid(&format!("{value} is outside {min}-{max}")))
+        }
+    };
+
+    let mut set = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, Some(step)),
+                _ => return Err(invalid("the step must be a positive number")),
+            },
+            None => (part, None),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            // `5/15` runs from 5 to the end of the range
+            None if step.is_some() => (value(range)?, max),
+            None => {
+                let value = value(range)?;
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(invalid("the range is backwards"));
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    /// 2024-05-01 12:00:00 UTC, a Wednesday
+    const NOON: i64 = 1_714_564_800;
+
+    fn next(expression: &str, after: i64) -> i64 {
+        let cron: Cron = expression.parse().unwrap();
+        cron.next_after(SlackTime::from_unix(after, 0))
+            .unwrap()
+            .timestamp()
+    }
+
+    #[test]
+    fn next_after() {
+        // strictly after, even when `after` matches
+        assert_eq!(next("* * * * *", NOON), NOON + 60);
+        assert_eq!(next("*/15 * * * *", NOON + 1), NOON + 15 * 60);
+        assert_eq!(next("0 9 * * *", NOON), NOON + 21 * 3600);
+        // Saturday 2024-05-04 at 08:00
+        assert_eq!(next("0 8 * * sat", NOON), NOON + 3 * 86_400 - 4 * 3600);
+        // Sunday, written as 7
+        assert_eq!(next("0 12 * * 7", NOON), NOON + 4 * 86_400);
+        // the 1st of the month or any Friday, whichever comes first
+        assert_eq!(next("0 12 1 * fri", NOON), NOON + 2 * 86_400);
+        assert_eq!(next("@yearly", NOON), 1_735_689_600);
+        // 2028-02-29
+        assert_eq!(next("0 0 29 feb *", NOON), 1_835_395_200);
+    }
+
+    #[test]
+    fn impossible() {
+        let cron: Cron = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(cron.next_after(SlackTime::from_unix(NOON, 0)), None);
+    }
+
+    #[test]
+    fn invalid() {
+        let err = |expression: &str| Cron::new(expression).unwrap_err().to_string();
+        assert_snapshot!(err("* * * *"), @"invalid cron expression: expected 5 fields, found 4: `* * * *`");
+        assert_snapshot!(err("60 * * * *"), @"invalid cron expression: invalid minute `60`: 60 is outside 0-59");
+        assert_snapshot!(err("* * * * */0"), @"invalid cron expression: invalid day of the week `*/0`: the step must be a positive number");
+        assert_snapshot!(err("* 5-1 * * *"), @"invalid cron expression: invalid hour `5-1`: the range is backwards");
+        assert_snapshot!(err("* * * smarch *"), @"invalid cron expression: invalid month `smarch`: unrecognized value");
+    }
+}
@@ -1,55 +1,150 @@
-use crate::{Error, Payload, Result};
-use chrono::NaiveDateTime;
-use reqwest::{Client, Url};
-use serde::{Serialize, Serializer};
-use std::fmt;
-
-/// Handles sending messages to slack
-#[derive(Debug, Clone)]
-pub struct Slack {
-    hook: Url,
-    client: Client,
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, NaiveDateTime, TimeZone};
+use serde::{
+    de::{self, Unexpected},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Slack timestamp
+///
+/// Sub-second precision is kept for the Web API's message timestamps, see
+/// [`SlackTime::message_ts()`], while attachments only accept whole seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SlackTime {
+    secs: i64,
+    nanos: u32,
 }
 
-impl Slack {
-    /// Construct a new instance of slack for a specific incoming url endpoint.
-    pub fn new<T: reqwest::IntoUrl>(hook: T) -> Result<Slack> {
-        Self::new_with_client(hook, Client::new())
+impl SlackTime {
+    /// Construct a new `SlackTime` from a naive date and time, which is assumed to be in UTC
+    ///
+    /// Use [`SlackTime::from_datetime()`] for times in any other time zone.
+    #[cfg(feature = "chrono")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "chrono")))]
+    pub fn new(time: &NaiveDateTime) -> SlackTime {
+        SlackTime::from_datetime(&time.and_utc())
     }
 
-    /// The same as [`Slack::new()`], but with a custom [`reqwest::Client`]
-    ///
-    /// This allows for configuring custom proxies, DNS resolvers, etc.
-    pub fn new_with_client<T: reqwest::IntoUrl>(hook: T, client: Client) -> Result<Self> {
-        let hook = hook.into_url()?;
-        Ok(Self { hook, client })
-    }
-
-    /// Send payload to slack service
-    pub async fn send(&self, payload: &Payload) -> Result<()> {
-        let response = self
-            .client
-            .post(self.hook.clone())
-            .json(payload)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            Err(Error::Slack(format!("HTTP error {}", response.status())))
+    /// Construct a new `SlackTime` from a date and time in any time zone
+    #[cfg(feature = "chrono")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "chrono")))]
+    pub fn from_datetime<Tz: TimeZone>(time: &DateTime<Tz>) -> SlackTime {
+        SlackTime::from_unix(time.timestamp(), time.timestamp_subsec_nanos())
+    }
+
+    /// Construct a new `SlackTime` from a `time::OffsetDateTime`
+    #[cfg(feature = "time")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "time")))]
+    pub fn from_offset_datetime(time: &time::OffsetDateTime) -> SlackTime {
+        SlackTime::from_unix(time.unix_timestamp(), time.nanosecond())
+    }
+
+    /// Construct a new `SlackTime` from a `SystemTime`
+    pub fn from_system_time(time: SystemTime) -> SlackTime {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => SlackTime::from_unix(since.as_secs() as i64, since.subsec_nanos()),
+            Err(err) => {
+                let before = err.duration();
+                match before.subsec_nanos() {
+                    0 => SlackTime::from_unix(-(before.as_secs() as i64), 0),
+                    nanos => {
+                        SlackTime::from_unix(-(before.as_secs() as i64) - 1, 1_000_000_000 - nanos)
+                    }
+                }
+            }
         }
     }
+
+    /// The current time
+    pub fn now() -> SlackTime {
+        SlackTime::from_system_time(SystemTime::now())
+    }
+
+    /// Construct a new `SlackTime` from seconds and nanoseconds since the unix epoch
+    pub fn from_unix(secs: i64, nanos: u32) -> SlackTime {
+        SlackTime {
+            secs: secs.saturating_add(i64::from(nanos / 1_000_000_000)),
+            nanos: nanos % 1_000_000_000,
+        }
+    }
+
+    /// Whole seconds since the unix epoch
+    pub fn timestamp(&self) -> i64 {
+        self.secs
+    }
+
+    /// Nanoseconds past [`SlackTime::timestamp()`]
+    pub fn subsec_nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    /// The equivalent `SystemTime`, or `None` if it is outside the range `SystemTime` can hold
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        let secs = Duration::from_secs(self.secs.unsigned_abs());
+        let since_secs = if self.secs >= 0 {
+            UNIX_EPOCH.checked_add(secs)
+        } else {
+            UNIX_EPOCH.checked_sub(secs)
+        };
+        since_secs?.checked_add(Duration::from_nanos(u64::from(self.nanos)))
+    }
+
+    /// Format as a message timestamp with microsecond precision, e.g. `1234567890.123456`
+    ///
+    /// This is the form the Web API uses to identify messages, e.g. `thread_ts`.
+    pub fn message_ts(&self) -> String {
+        format!("{}.{:06}", self.secs, self.nanos / 1_000)
+    }
+
+    fn from_fractional(ts: f64) -> SlackTime {
+        let secs = ts.floor();
+        // rounding can reach a whole second, which `from_unix` carries into `secs`
+        SlackTime::from_unix(secs as i64, ((ts - secs) * 1e9).round() as u32)
+    }
 }
 
-/// Slack timestamp
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub struct SlackTime(NaiveDateTime);
+#[cfg(feature = "chrono")]
+impl From<&NaiveDateTime> for SlackTime {
+    fn from(time: &NaiveDateTime) -> SlackTime {
+        SlackTime::new(time)
+    }
+}
 
-impl SlackTime {
-    /// Construct a new `SlackTime`
-    pub fn new(time: &NaiveDateTime) -> SlackTime {
-        SlackTime(*time)
+#[cfg(feature = "chrono")]
+impl From<NaiveDateTime> for SlackTime {
+    fn from(time: NaiveDateTime) -> SlackTime {
+        SlackTime::new(&time)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: TimeZone> From<&DateTime<Tz>> for SlackTime {
+    fn from(time: &DateTime<Tz>) -> SlackTime {
+        SlackTime::from_datetime(time)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: TimeZone> From<DateTime<Tz>> for SlackTime {
+    fn from(time: DateTime<Tz>) -> SlackTime {
+        SlackTime::from_datetime(&time)
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for SlackTime {
+    fn from(time: time::OffsetDateTime) -> SlackTime {
+        SlackTime::from_offset_datetime(&time)
+    }
+}
+
+impl From<SystemTime> for SlackTime {
+    fn from(time: SystemTime) -> SlackTime {
+        SlackTime::from_system_time(time)
     }
 }
 
@@ -58,13 +153,46 @@ impl Serialize for SlackTime {
     where
         S: Serializer,
     {
-        serializer.serialize_i64(self.0.and_utc().timestamp())
+        serializer.serialize_i64(self.secs)
+    }
+}
+
+impl<'de> Deserialize<'de> for SlackTime {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // some methods, e.g. `chat.scheduleMessage`, send the seconds as a string
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Timestamp {
+            Secs(i64),
+            Fractional(f64),
+            Text(String),
+        }
+
+        Ok(match Timestamp::deserialize(deserializer)? {
+            Timestamp::Secs(secs) => SlackTime::from_unix(secs, 0),
+            Timestamp::Fractional(ts) => SlackTime::from_fractional(ts),
+            Timestamp::Text(text) => match (text.parse(), text.parse::<f64>()) {
+                (Ok(secs), _) => SlackTime::from_unix(secs, 0),
+                (_, Ok(ts)) if ts.is_finite() => SlackTime::from_fractional(ts),
+                _ => {
+                    return Err(de::Error::invalid_value(
+                        Unexpected::Str(&text),
+                        &"a unix timestamp",
+                    ))
+                }
+            },
+        })
     }
 }
 
 /// Representation of any text sent through slack
 /// the text must be processed to escape specific characters
-#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+///
+/// Deserialized text is assumed to already be escaped, so it is kept as-is.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct SlackText(String);
 
 impl SlackText {
@@ -84,7 +212,7 @@ impl SlackText {
         SlackText(s)
     }
 
-    fn new_raw<S: Into<String>>(text: S) -> SlackText {
+    pub(crate) fn new_raw<S: Into<String>>(text: S) -> SlackText {
         SlackText(text.into())
     }
 }
@@ -112,6 +240,8 @@ pub enum SlackTextContent {
     Link(SlackLink),
     /// User Link
     User(SlackUserLink),
+    /// Channel Link
+    Channel(SlackChannelLink),
 }
 
 impl From<&[SlackTextContent]> for SlackText {
@@ -122,6 +252,7 @@ impl From<&[SlackTextContent]> for SlackText {
                 SlackTextContent::Text(s) => s.to_string(),
                 SlackTextContent::Link(link) => link.to_string(),
                 SlackTextContent::User(u) => u.to_string(),
+                SlackTextContent::Channel(c) => c.to_string(),
             })
             .collect::<Vec<String>>()
             .join(" ");
@@ -206,18 +337,42 @@ impl Serialize for SlackUserLink {
     }
 }
 
+/// Representation of a channel mention, e.g. `<#C0123456>`, which slack shows as the channel's
+/// current name
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SlackChannelLink {
+    /// Channel ID (C0123456) style
+    pub id: String,
+}
+
+impl SlackChannelLink {
+    /// Construct new `SlackChannelLink` with a string slice
+    pub fn new(id: &str) -> SlackChannelLink {
+        SlackChannelLink { id: id.to_owned() }
+    }
+}
+
+impl fmt::Display for SlackChannelLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<#{}>", self.id)
+    }
+}
+
+impl Serialize for SlackChannelLink {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::slack::{Slack, SlackLink};
+    use crate::slack::{SlackChannelLink, SlackLink, SlackTime};
     use crate::{AttachmentBuilder, Field, Parse, PayloadBuilder, SlackText};
-    use chrono::DateTime;
     use insta::{assert_json_snapshot, assert_snapshot};
-
-    #[test]
-    fn slack_incoming_url() {
-        let s = Slack::new("https://hooks.slack.com/services/abc/123/45z").unwrap();
-        assert_snapshot!(s.hook, @"https://hooks.slack.com/services/abc/123/45z");
-    }
+    use std::time::{Duration, UNIX_EPOCH};
 
     #[test]
     fn slack_text() {
@@ -250,9 +405,7 @@ mod test {
             .color("#6800e8")
             .fields(vec![Field::new("title", "value", None)])
             .title_link("https://title_link.com/")
-            .ts(&DateTime::from_timestamp(123_456_789, 0)
-                .unwrap()
-                .naive_utc())
+            .ts(UNIX_EPOCH + Duration::from_secs(123_456_789))
             .build()
             .unwrap()];
 
@@ -315,6 +468,69 @@ mod test {
         );
     }
 
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn slack_time_keeps_offset() {
+        let local = chrono::DateTime::parse_from_rfc3339("2001-09-09T03:46:40.5+02:00").unwrap();
+        let t = SlackTime::from(local);
+        assert_json_snapshot!(t, @"1000000000");
+        assert_snapshot!(t.message_ts(), @"1000000000.500000");
+        assert_eq!(t, SlackTime::new(&local.naive_utc()));
+    }
+
+    #[test]
+    fn slack_time_system_time() {
+        let t = SlackTime::from(UNIX_EPOCH + Duration::from_millis(1_500));
+        assert_snapshot!(t.message_ts(), @"1.500000");
+        assert_eq!(
+            t.to_system_time(),
+            Some(UNIX_EPOCH + Duration::from_millis(1_500))
+        );
+
+        let before_epoch = SlackTime::from(UNIX_EPOCH - Duration::from_millis(1_500));
+        assert_eq!(
+            (before_epoch.timestamp(), before_epoch.subsec_nanos()),
+            (-2, 500_000_000)
+        );
+        assert_eq!(
+            before_epoch.to_system_time(),
+            Some(UNIX_EPOCH - Duration::from_millis(1_500))
+        );
+
+        // how far `SystemTime` reaches depends on the platform, so only check these don't panic
+        for secs in [i64::MIN, i64::MAX] {
+            let _ = SlackTime::from_unix(secs, 999_999_999).to_system_time();
+        }
+    }
+
+    #[test]
+    fn slack_time_fractional_rounds_up() {
+        let t: SlackTime = serde_json::from_str("1.9999999999").unwrap();
+        assert_eq!((t.timestamp(), t.subsec_nanos()), (2, 0));
+        let t: SlackTime = serde_json::from_str("-0.25").unwrap();
+        assert_eq!((t.timestamp(), t.subsec_nanos()), (-1, 750_000_000));
+    }
+
+    #[test]
+    fn slack_time_from_string() {
+        let t: SlackTime = serde_json::from_str(r#""1562180400""#).unwrap();
+        assert_eq!((t.timestamp(), t.subsec_nanos()), (1_562_180_400, 0));
+        let t: SlackTime = serde_json::from_str(r#""1562180400.25""#).unwrap();
+        assert_eq!(
+            (t.timestamp(), t.subsec_nanos()),
+            (1_562_180_400, 250_000_000)
+        );
+        assert!(serde_json::from_str::<SlackTime>(r#""soon""#).is_err());
+        assert!(serde_json::from_str::<SlackTime>(r#""NaN""#).is_err());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn slack_time_offset_datetime() {
+        let t = SlackTime::from(time::OffsetDateTime::from_unix_timestamp(123_456_789).unwrap());
+        assert_json_snapshot!(t, @"123456789");
+    }
+
     #[test]
     fn slack_text_content() {
         use super::SlackTextContent;
@@ -326,4 +542,15 @@ mod test {
         let st = SlackText::from(&message[..]);
         assert_snapshot!(st, @"moo &lt;&amp;&gt; moo <@USER|M&lt;E&gt;> wow.");
     }
+
+    #[test]
+    fn slack_channel_link() {
+        use super::SlackTextContent;
+        let message = [
+            SlackTextContent::Text("see".into()),
+            SlackTextContent::Channel(SlackChannelLink::new("C0123")),
+        ];
+        let st = SlackText::from(&message[..]);
+        assert_snapshot!(st, @"see <#C0123>");
+    }
 }
@@ -0,0 +1,660 @@
+//! Render payloads as [Microsoft Teams](https://learn.microsoft.com/en-us/microsoftteams/platform/webhooks-and-connectors/what-are-webhooks-and-connectors)
+//! cards, so that the same notification code can target Teams as well as slack
+//!
+//! Each [`Attachment`] becomes its own part of the card: the `title`, `text` and `author_name`
+//! become text, the `fields` become facts, `image_url` becomes an image and `title_link` becomes
+//! an action opening the link. Blocks, legacy actions and the thumbnail are left out.
+//!
+//! ```
+//! use slack_hook::teams::{self, CardFormat};
+//! use slack_hook::{AttachmentBuilder, Field, PayloadBuilder, SlackColor};
+//!
+//! let attachment = AttachmentBuilder::new("Deploy failed")
+//!     .title("Deploy #42")
+//!     .title_link("https://ci.example.com/42")
+//!     .color(SlackColor::Danger)
+//!     .fields(vec![Field::new("env", "prod", Some(true))])
+//!     .build()
+//!     .unwrap();
+//! let payload = PayloadBuilder::new()
+//!     .attachments(vec![attachment])
+//!     .build()
+//!     .unwrap();
+//! let card = teams::render(&payload, CardFormat::AdaptiveCard);
+//! assert_eq!(card["type"], "message");
+//! ```
+
+use crate::{Attachment, HexColor, Payload, SlackColor, SlackText};
+#[cfg(feature = "client")]
+use crate::{Error, Result};
+#[cfg(feature = "client")]
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+#[cfg(feature = "client")]
+use std::fmt;
+
+const ADAPTIVE_CARD_CONTENT_TYPE: &str = "application/vnd.microsoft.card.adaptive";
+const ADAPTIVE_CARD_VERSION: &str = "1.4";
+
+/// The card formats Teams webhooks accept
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum CardFormat {
+    /// An [Adaptive Card](https://adaptivecards.io/), as taken by webhooks created with the
+    /// Workflows app
+    #[default]
+    AdaptiveCard,
+    /// The legacy [actionable message card](https://learn.microsoft.com/en-us/outlook/actionable-messages/message-card-reference),
+    /// as taken by Office 365 connector webhooks
+    MessageCard,
+}
+
+/// Render a payload as the JSON body of a Teams webhook request
+pub fn render(payload: &Payload, format: CardFormat) -> Value {
+    match format {
+        CardFormat::AdaptiveCard => adaptive_card(payload),
+        CardFormat::MessageCard => message_card(payload),
+    }
+}
+
+fn adaptive_card(payload: &Payload) -> Value {
+    let mut body = Vec::new();
+    if let Some(text) = &payload.text {
+        body.push(text_block(text, &[]));
+    }
+
+    let mut actions = Vec::new();
+    for attachment in payload.attachments.iter().flatten() {
+        let mut items = Vec::new();
+        if let Some(pretext) = &attachment.pretext {
+            items.push(text_block(pretext, &[("isSubtle", json!(true))]));
+        }
+        if let Some(author_name) = &attachment.author_name {
+            items.push(text_block(author_name, &[("size", json!("small"))]));
+        }
+        if let Some(title) = &attachment.title {
+            items.push(text_block(
+                title,
+                &[("size", json!("medium")), ("weight", json!("bolder"))],
+            ));
+        }
+        if let Some(text) = &attachment.text {
+            items.push(text_block(text, &[]));
+        }
+        if let Some(fields) = attachment
+            .fields
+            .as_ref()
+            .filter(|fields| !fields.is_empty())
+        {
+            let facts: Vec<_> = fields
+                .iter()
+                .map(|field| json!({ "title": field.title, "value": markdown(&field.value) }))
+                .collect();
+            items.push(json!({ "type": "FactSet", "facts": facts }));
+        }
+        if let Some(image_url) = &attachment.image_url {
+            items.push(json!({ "type": "Image", "url": image_url }));
+        }
+        if let Some(footer) = &attachment.footer {
+            items.push(text_block(
+                footer,
+                &[("size", json!("small")), ("isSubtle", json!(true))],
+            ));
+        }
+        if items.is_empty() {
+            items.push(text_block(&attachment.fallback, &[]));
+        }
+
+        let mut container = Map::new();
+        let _ = container.insert("type".to_owned(), json!("Container"));
+        if let Some(color) = attachment.color {
+            let _ = container.insert("style".to_owned(), json!(container_style(color)));
+        }
+        let _ = container.insert("items".to_owned(), Value::Array(items));
+        body.push(Value::Object(container));
+
+        if let Some(action) = open_url(attachment) {
+            actions.push(json!({
+                "type": "Action.OpenUrl",
+                "title": action.0,
+                "url": action.1,
+            }));
+        }
+    }
+
+    let mut content = Map::new();
+    let _ = content.insert(
+        "$schema".to_owned(),
+        json!("http://adaptivecards.io/schemas/adaptive-card.json"),
+    );
+    let _ = content.insert("type".to_owned(), json!("AdaptiveCard"));
+    let _ = content.insert("version".to_owned(), json!(ADAPTIVE_CARD_VERSION));
+    let _ = content.insert("body".to_owned(), Value::Array(body));
+    if !actions.is_empty() {
+        let _ = content.insert("actions".to_owned(), Value::Array(actions));
+    }
+
+    json!({
+        "type": "message",
+        "attachments": [{
+            "contentType": ADAPTIVE_CARD_CONTENT_TYPE,
+            "content": content,
+        }],
+    })
+}
+
+fn message_card(payload: &Payload) -> Value {
+    let attachments = payload.attachments.as_deref().unwrap_or_default();
+    let summary = payload
+        .text
+        .as_ref()
+        .or_else(|| attachments.first().map(|attachment| &attachment.fallback))
+        .map(plain)
+        .unwrap_or_default();
+
+    let mut card = Map::new();
+    let _ = card.insert("@type".to_owned(), json!("MessageCard"));
+    let _ = card.insert(
+        "@context".to_owned(),
+        json!("https://schema.org/extensions"),
+    );
+    let _ = card.insert("summary".to_owned(), json!(summary));
+    // a card only has one color, so the first attachment's is used
+    if let Some(color) = attachments.iter().find_map(|attachment| attachment.color) {
+        let (red, green, blue) = color.rgb();
+        let _ = card.insert(
+            "themeColor".to_owned(),
+            json!(HexColor::from_rgb(red, green, blue).to_string()),
+        );
+    }
+    if let Some(text) = &payload.text {
+        let _ = card.insert("text".to_owned(), json!(markdown(text)));
+    }
+
+    let mut sections = Vec::new();
+    let mut actions = Vec::new();
+    for attachment in attachments {
+        let mut section = Map::new();
+        let mut insert = |key: &str, text: &Option<SlackText>| {
+            if let Some(text) = text {
+                let _ = section.insert(key.to_owned(), json!(markdown(text)));
+            }
+        };
+        insert("activityTitle", &attachment.author_name);
+        insert("activitySubtitle", &attachment.pretext);
+        insert("title", &attachment.title);
+        insert("text", &attachment.text);
+        if let Some(author_icon) = &attachment.author_icon {
+            let _ = section.insert("activityImage".to_owned(), json!(author_icon));
+        }
+        if let Some(fields) = attachment
+            .fields
+            .as_ref()
+            .filter(|fields| !fields.is_empty())
+        {
+            let facts: Vec<_> = fields
+                .iter()
+                .map(|field| json!({ "name": field.title, "value": markdown(&field.value) }))
+                .collect();
+            let _ = section.insert("facts".to_owned(), Value::Array(facts));
+        }
+        if let Some(image_url) = &attachment.image_url {
+            let _ = section.insert("images".to_owned(), json!([{ "image": image_url }]));
+        }
+        if section.is_empty() {
+            let _ = section.insert("text".to_owned(), json!(markdown(&attachment.fallback)));
+        }
+        sections.push(Value::Object(section));
+
+        if let Some(action) = open_url(attachment) {
+            actions.push(json!({
+                "@type": "OpenUri",
+                "name": action.0,
+                "targets": [{ "os": "default", "uri": action.1 }],
+            }));
+        }
+    }
+    if !sections.is_empty() {
+        let _ = card.insert("sections".to_owned(), Value::Array(sections));
+    }
+    if !actions.is_empty() {
+        let _ = card.insert("potentialAction".to_owned(), Value::Array(actions));
+    }
+    Value::Object(card)
+}
+
+fn text_block(text: &SlackText, extra: &[(&str, Value)]) -> Value {
+    let mut block = Map::new();
+    let _ = block.insert("type".to_owned(), json!("TextBlock"));
+    let _ = block.insert("text".to_owned(), json!(markdown(text)));
+    let _ = block.insert("wrap".to_owned(), json!(true));
+    for (key, value) in extra {
+        let _ = block.insert((*key).to_owned(), value.clone());
+    }
+    Value::Object(block)
+}
+
+/// The name and url of the action opening an attachment's `title_link`
+fn open_url(attachment: &Attachment) -> Option<(String, String)> {
+    let link = attachment.title_link.as_ref()?;
+    let name = attachment
+        .title
+        .as_ref()
+        .map_or_else(|| "Open".to_owned(), plain);
+    Some((name, link.to_string()))
+}
+
+/// Adaptive Cards can't be any color, so use the container style closest to `color`
+fn container_style(color: HexColor) -> &'static str {
+    let styles = [
+        ("good", HexColor::from(SlackColor::Good)),
+        ("warning", HexColor::from(SlackColor::Warning)),
+        ("attention", HexColor::from(SlackColor::Danger)),
+        ("accent", HexColor::from_rgb(0x00, 0x78, 0xd4)),
+    ];
+    let distance = |other: &HexColor| {
+        let [a, b] = [color.rgb(), other.rgb()];
+        let square = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2);
+        square(a.0, b.0) + square(a.1, b.1) + square(a.2, b.2)
+    };
+    styles
+        .iter()
+        .min_by_key(|(_, style)| distance(style))
+        .map_or("default", |(name, _)| name)
+}
+
+/// Convert slack's markup to the markdown Teams renders
+///
+/// Links like `<https://example.com|text>` become `[text](https://example.com)`, mentions and
+/// dates show their label, and the escaped `&`, `<` and `>` are unescaped.
+fn markdown(text: &SlackText) -> String {
+    convert(text, true)
+}
+
+/// The text without any markup, e.g. for a card's summary
+fn plain(text: &SlackText) -> String {
+    convert(text, false)
+}
+
+fn convert(text: &SlackText, links: bool) -> String {
+    let text = text.to_string();
+    let mut converted = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find('<') {
+        converted.push_str(&unescape(&rest[..start]));
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let inner = &rest[start + 1..start + end];
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(unescape(label))),
+            None => (inner, None),
+        };
+        match (target.chars().next(), label) {
+            // `<!here>`, `<@U0123|alice>`, `<#C0123|general>` and `<!date^...|fallback>`
+            (Some('@' | '#'), Some(label)) => {
+                converted.push_str(&target[..1]);
+                converted.push_str(label.trim_start_matches(['@', '#']));
+            }
+            (Some('!'), Some(label)) => converted.push_str(&label),
+            (Some('@' | '#'), None) => converted.push_str(target),
+            (Some('!'), None) => {
+                converted.push('@');
+                converted.push_str(&target[1..]);
+            }
+            (_, Some(label)) if links => {
+                converted.push_str(&format!("[{label}]({})", unescape(target)));
+            }
+            (_, Some(label)) => converted.push_str(&label),
+            (_, None) => converted.push_str(&unescape(target)),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    converted.push_str(&unescape(rest));
+    converted
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Sends payloads to a Microsoft Teams webhook, rendered as cards
+///
+/// This mirrors [`Slack`](crate::Slack), so the same notification code can target either.
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+#[derive(Clone)]
+pub struct Teams {
+    url: Url,
+    format: CardFormat,
+    client: Client,
+    defaults: Option<Payload>,
+}
+
+#[cfg(feature = "client")]
+impl Teams {
+    /// Construct a sender for a Teams webhook url, rendering payloads in `format`
+    ///
+    /// Webhooks created with the Workflows app take [`CardFormat::AdaptiveCard`], while legacy
+    /// Office 365 connectors take [`CardFormat::MessageCard`].
+    pub fn new(url: &str, format: CardFormat) -> Result<Teams> {
+        Self::new_with_client(url, format, Client::new())
+    }
+
+    /// The same as [`Teams::new()`], but with a custom [`reqwest::Client`]
+    pub fn new_with_client(url: &str, format: CardFormat, client: Client) -> Result<Teams> {
+        let url = Url::parse(url)?;
+        if url.scheme() != "https" {
+            return Err(Error::WebhookUrl(format!(
+                "Must use https: found `{}`",
+                url.scheme()
+            )));
+        }
+        Ok(Teams {
+            url,
+            format,
+            client,
+            defaults: None,
+        })
+    }
+
+    /// Set a template [`Payload`] that is merged into every payload sent
+    ///
+    /// See [`Payload::with_defaults()`] for how the fields are merged.
+    pub fn with_defaults(mut self, defaults: Payload) -> Teams {
+        self.defaults = Some(defaults);
+        self
+    }
+
+    /// The template [`Payload`] merged into every payload sent, if any
+    pub fn defaults(&self) -> Option<&Payload> {
+        self.defaults.as_ref()
+    }
+
+    /// The card format payloads are rendered in
+    pub fn format(&self) -> CardFormat {
+        self.format
+    }
+
+    /// Render a payload as a card and send it to Teams
+    ///
+    /// The payload is merged with the [`Teams::defaults()`] first, if any are set.
+    pub async fn send(&self, payload: &Payload) -> Result<()> {
+        let card = match &self.defaults {
+            Some(defaults) => render(&payload.with_defaults(defaults), self.format),
+            None => render(payload, self.format),
+        };
+        let response = self
+            .client
+            .post(self.url.clone())
+            .json(&card)
+            .send()
+            .await
+            // the whole url is the secret
+            .map_err(|err| Error::from(err.without_url()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = response.text().await.unwrap_or_default();
+        let message = match body.trim() {
+            "" => format!("HTTP error {status}"),
+            body => format!("HTTP error {status}: {body}"),
+        };
+        Err(Error::Teams(message))
+    }
+}
+
+#[cfg(feature = "client")]
+impl fmt::Debug for Teams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the whole url is the secret, including its query's signature
+        let host = self.url.host_str().unwrap_or_default();
+        f.debug_struct("Teams")
+            .field("url", &format!("https://{host}/[redacted]"))
+            .field("format", &self.format)
+            .field("defaults", &self.defaults)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AttachmentBuilder, Field, PayloadBuilder, SlackLink, SlackTextContent};
+    use insta::{assert_json_snapshot, assert_snapshot};
+
+    fn payload() -> Payload {
+        let link = SlackLink::new("https://status.example.com", "status page");
+        let text = [
+            SlackTextContent::Text("Deploy <api> failed, see the".into()),
+            SlackTextContent::Link(link),
+        ];
+        let attachment = AttachmentBuilder::new("Deploy #42 failed")
+            .pretext("Heads up")
+            .author_name("ci")
+            .author_icon("https://ci.example.com/icon.png")
+            .title("Deploy #42")
+            .title_link("https://ci.example.com/42")
+            .text("Health checks failed")
+            .color(SlackColor::Danger)
+            .fields(vec![
+                Field::new("env", "prod", Some(true)),
+                Field::new("region", "us-east-1", Some(true)),
+            ])
+            .image_url("https://ci.example.com/42/graph.png")
+            .footer("ci")
+            .build()
+            .unwrap();
+        PayloadBuilder::new()
+            .text(text.as_slice())
+            .attachments(vec![attachment])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn adaptive_card() {
+        assert_json_snapshot!(render(&payload(), CardFormat::AdaptiveCard), @r###"
+        {
+          "attachments": [
+            {
+              "content": {
+                "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                "actions": [
+                  {
+                    "title": "Deploy #42",
+                    "type": "Action.OpenUrl",
+                    "url": "https://ci.example.com/42"
+                  }
+                ],
+                "body": [
+                  {
+                    "text": "Deploy <api> failed, see the [status page](https://status.example.com)",
+                    "type": "TextBlock",
+                    "wrap": true
+                  },
+                  {
+                    "items": [
+                      {
+                        "isSubtle": true,
+                        "text": "Heads up",
+                        "type": "TextBlock",
+                        "wrap": true
+                      },
+                      {
+                        "size": "small",
+                        "text": "ci",
+                        "type": "TextBlock",
+                        "wrap": true
+                      },
+                      {
+                        "size": "medium",
+                        "text": "Deploy #42",
+                        "type": "TextBlock",
+                        "weight": "bolder",
+                        "wrap": true
+                      },
+                      {
+                        "text": "Health checks failed",
+                        "type": "TextBlock",
+                        "wrap": true
+                      },
+                      {
+                        "facts": [
+                          {
+                            "title": "env",
+                            "value": "prod"
+                          },
+                          {
+                            "title": "region",
+                            "value": "us-east-1"
+                          }
+                        ],
+                        "type": "FactSet"
+                      },
+                      {
+                        "type": "Image",
+                        "url": "https://ci.example.com/42/graph.png"
+                      },
+                      {
+                        "isSubtle": true,
+                        "size": "small",
+                        "text": "ci",
+                        "type": "TextBlock",
+                        "wrap": true
+                      }
+                    ],
+                    "style": "attention",
+                    "type": "Container"
+                  }
+                ],
+                "type": "AdaptiveCard",
+                "version": "1.4"
+              },
+              "contentType": "application/vnd.microsoft.card.adaptive"
+            }
+          ],
+          "type": "message"
+        }
+        "###);
+    }
+
+    #[test]
+    fn message_card() {
+        assert_json_snapshot!(render(&payload(), CardFormat::MessageCard), @r###"
+        {
+          "@context": "https://schema.org/extensions",
+          "@type": "MessageCard",
+          "potentialAction": [
+            {
+              "@type": "OpenUri",
+              "name": "Deploy #42",
+              "targets": [
+                {
+                  "os": "default",
+                  "uri": "https://ci.example.com/42"
+                }
+              ]
+            }
+          ],
+          "sections": [
+            {
+              "activityImage": "https://ci.example.com/icon.png",
+              "activitySubtitle": "Heads up",
+              "activityTitle": "ci",
+              "facts": [
+                {
+                  "name": "env",
+                  "value": "prod"
+                },
+                {
+                  "name": "region",
+                  "value": "us-east-1"
+                }
+              ],
+              "images": [
+                {
+                  "image": "https://ci.example.com/42/graph.png"
+                }
+              ],
+              "text": "Health checks failed",
+              "title": "Deploy #42"
+            }
+          ],
+          "summary": "Deploy <api> failed, see the status page",
+          "text": "Deploy <api> failed, see the [status page](https://status.example.com)",
+          "themeColor": "#a30200"
+        }
+        "###);
+    }
+
+    #[test]
+    fn converts_markup() {
+        let text = SlackText::new_raw(
+            "<!here> <@U0123|alice> in <#C0123|general> &amp; <https://example.com> \
+             <!date^1714564800^{date}|May 1st> a &lt;b&gt;",
+        );
+        assert_snapshot!(markdown(&text), @"@here @alice in #general & https://example.com May 1st a <b>");
+        assert_snapshot!(plain(&SlackText::new_raw("see <https://example.com|docs>")), @"see docs");
+    }
+
+    #[test]
+    fn nearest_container_style() {
+        assert_eq!(container_style(SlackColor::Good.into()), "good");
+        assert_eq!(container_style("#3b5998".parse().unwrap()), "accent");
+        assert_eq!(container_style("red".parse().unwrap()), "attention");
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn send() {
+        use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(400).set_body_string("Summary or Text is required."),
+            )
+            .mount(&server)
+            .await;
+
+        let teams = Teams {
+            url: server.uri().parse().unwrap(),
+            format: CardFormat::MessageCard,
+            client: Client::new(),
+            defaults: None,
+        };
+        let err = teams.send(&Payload::default()).await.unwrap_err();
+        assert_snapshot!(err, @"teams service error: HTTP error 400 Bad Request: Summary or Text is required.");
+
+        let requests = server.received_requests().await.unwrap();
+        let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_json_snapshot!(body, @r###"
+        {
+          "@context": "https://schema.org/extensions",
+          "@type": "MessageCard",
+          "summary": ""
+        }
+        "###);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn transport_error_hides_url() {
+        let teams = Teams {
+            url: crate::test_util::refused_url("/webhookb2/s3cr3t"),
+            format: CardFormat::AdaptiveCard,
+            client: Client::new(),
+            defaults: None,
+        };
+        let err = teams.send(&Payload::default()).await.unwrap_err();
+        let err = format!("{err} {err:?}");
+        assert!(!err.contains("s3cr3t"), "{}", err);
+    }
+}
@@ -0,0 +1,90 @@
+//! Helpers shared by the unit tests
+
+use crate::WebhookUrl;
+#[cfg(any(
+    feature = "dedup",
+    feature = "digest",
+    all(feature = "outbox", feature = "client")
+))]
+use serde_json::Value;
+use std::net::TcpListener;
+#[cfg(any(
+    feature = "dedup",
+    feature = "digest",
+    feature = "scheduler",
+    all(feature = "outbox", feature = "client")
+))]
+use std::time::{Duration, Instant};
+use url::Url;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+/// A url on a port nothing listens on, so requests to it fail to connect, ending in `path`
+pub(crate) fn refused_url(path: &str) -> Url {
+    // the port is free again once the listener is dropped
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    format!("http://127.0.0.1:{port}{path}").parse().unwrap()
+}
+
+/// A webhook url pointing at `server`
+pub(crate) fn hook_url(server: &MockServer) -> WebhookUrl {
+    WebhookUrl::new_unchecked(server.uri().parse().unwrap())
+}
+
+/// Have `server` answer every post not matched by an earlier mock with `status`
+pub(crate) async fn respond_with(server: &MockServer, status: u16) {
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(status))
+        .mount(server)
+        .await;
+}
+
+/// A mock webhook answering every post with `status`, along with its url
+pub(crate) async fn mock_hook(status: u16) -> (MockServer, WebhookUrl) {
+    let server = MockServer::start().await;
+    respond_with(&server, status).await;
+    let hook = hook_url(&server);
+    (server, hook)
+}
+
+/// Wait for `done` to hold, failing the test if it takes more than a few seconds
+///
+/// The deadline is in real time, so this works with tokio's clock paused while requests are in
+/// flight.
+#[cfg(any(feature = "scheduler", all(feature = "outbox", feature = "client")))]
+pub(crate) async fn wait_for<F: FnMut() -> bool>(mut done: F) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Wait for `server` to receive at least `count` requests, returning all of their bodies
+#[cfg(any(
+    feature = "dedup",
+    feature = "digest",
+    all(feature = "outbox", feature = "client")
+))]
+pub(crate) async fn received(server: &MockServer, count: usize) -> Vec<Value> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let requests = server.received_requests().await.unwrap();
+        if requests.len() >= count {
+            return requests
+                .iter()
+                .map(|request| serde_json::from_slice(&request.body).unwrap())
+                .collect();
+        }
+        assert!(
+            Instant::now() < deadline,
+            "expected {} requests, received {}",
+            count,
+            requests.len()
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
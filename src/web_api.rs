@@ -0,0 +1,414 @@
+//! Act on posted messages through slack's [Web API](https://api.slack.com/web)
+//!
+//! ```rust,no_run
+//! # async fn run() -> slack_hook::Result<()> {
+//! use slack_hook::web_api::{MessageRef, WebApi};
+//!
+//! let api = WebApi::new("xoxb-bot-token");
+//! let incident = MessageRef::new("C0123456", "1714564800.000100");
+//! api.add_reaction(&incident, "white_check_mark").await?;
+//! api.pin(&incident).await?;
+//! println!("{}", api.permalink(&incident).await?);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api;
+use crate::{MessageMetadata, Result};
+use reqwest::{Client, Url};
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Deserialize, Serialize,
+};
+use std::fmt;
+
+mod files;
+mod resolver;
+mod scheduled;
+
+pub use self::files::{FileUpload, UploadedFile};
+pub use self::resolver::Resolver;
+pub use self::scheduled::ScheduledMessage;
+
+/// A Web API client authenticated with a bot or user token (`xoxb-...`/`xoxp-...`)
+///
+/// Method failures reported by slack, like `channel_not_found`, are returned as
+/// [`Error::Api`](crate::Error::Api).
+#[derive(Clone)]
+pub struct WebApi {
+    token: String,
+    client: Client,
+    api_url: Url,
+}
+
+impl WebApi {
+    /// Construct a new Web API client from a token
+    pub fn new<S: Into<String>>(token: S) -> WebApi {
+        Self::new_with_client(token, Client::new())
+    }
+
+    /// The same as [`WebApi::new()`], but with a custom [`reqwest::Client`]
+    pub fn new_with_client<S: Into<String>>(token: S, client: Client) -> WebApi {
+        WebApi {
+            token: token.into(),
+            client,
+            api_url: api::default_api_url(),
+        }
+    }
+
+    /// Use a different base url for the Web API, e.g. `https://slack-gov.com/api/`
+    pub fn api_url(mut self, api_url: Url) -> WebApi {
+        self.api_url = api::normalize_api_url(api_url);
+        self
+    }
+
+    /// Add an emoji reaction, e.g. `thumbsup`, to a message
+    pub async fn add_reaction(&self, message: &MessageRef, name: &str) -> Result<()> {
+        let body = Reaction {
+            message: message.into(),
+            name: name.trim_matches(':'),
+        };
+        self.call_ok("reactions.add", &body).await
+    }
+
+    /// Remove an emoji reaction added by this token's user
+    pub async fn remove_reaction(&self, message: &MessageRef, name: &str) -> Result<()> {
+        let body = Reaction {
+            message: message.into(),
+            name: name.trim_matches(':'),
+        };
+        self.call_ok("reactions.remove", &body).await
+    }
+
+    /// Pin a message to its channel
+    pub async fn pin(&self, message: &MessageRef) -> Result<()> {
+        self.call_ok("pins.add", &Timestamped::from(message)).await
+    }
+
+    /// Unpin a message from its channel
+    pub async fn unpin(&self, message: &MessageRef) -> Result<()> {
+        self.call_ok("pins.remove", &Timestamped::from(message))
+            .await
+    }
+
+    /// Get a link to a message
+    pub async fn permalink(&self, message: &MessageRef) -> Result<Url> {
+        let body = [
+            ("channel", message.channel.as_str()),
+            ("message_ts", message.ts.as_str()),
+        ];
+        let response: Permalink = api::post_form(
+            &self.client,
+            &self.api_url,
+            &self.token,
+            "chat.getPermalink",
+            &body,
+        )
+        .await?;
+        Ok(response.permalink)
+    }
+
+    /// Read back the [`MessageMetadata`] attached to a message, if it has any
+    ///
+    /// ```rust,no_run
+    /// # async fn run() -> slack_hook::Result<()> {
+    /// use slack_hook::web_api::{MessageRef, WebApi};
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct Build {
+    ///     version: String,
+    /// }
+    ///
+    /// let api = WebApi::new("xoxb-bot-token");
+    /// let announcement = MessageRef::new("C0123456", "1714564800.000100");
+    /// if let Some(metadata) = api.message_metadata::<Build>(&announcement).await? {
+    ///     println!("{} {}", metadata.event_type, metadata.event_payload.version);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn message_metadata<T: DeserializeOwned>(
+        &self,
+        message: &MessageRef,
+    ) -> Result<Option<MessageMetadata<T>>> {
+        let body = [
+            ("channel", message.channel.as_str()),
+            ("latest", message.ts.as_str()),
+            ("oldest", message.ts.as_str()),
+            ("inclusive", "true"),
+            ("limit", "1"),
+            ("include_all_metadata", "true"),
+        ];
+        let history: History = api::post_form(
+            &self.client,
+            &self.api_url,
+            &self.token,
+            "conversations.history",
+            &body,
+        )
+        .await?;
+        history
+            .messages
+            .into_iter()
+            .find(|found| found.ts == message.ts)
+            .and_then(|found| found.metadata)
+            .map(|metadata| metadata.parse())
+            .transpose()
+    }
+
+    /// Call a method whose response only says whether it worked
+    async fn call_ok<B: Serialize + ?Sized>(&self, method: &str, body: &B) -> Result<()> {
+        let IgnoredAny = self.call(method, body).await?;
+        Ok(())
+    }
+
+    async fn call<B, T>(&self, method: &str, body: &B) -> Result<T>
+    where
+        B: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        api::post_json(&self.client, &self.api_url, &self.token, method, body).await
+    }
+}
+
+impl fmt::Debug for WebApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebApi")
+            .field("token", &"[redacted]")
+            .field("api_url", &self.api_url)
+            .finish()
+    }
+}
+
+/// A posted message, identified by its channel and timestamp
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MessageRef {
+    /// Id of the channel the message was posted to, e.g. `C0123456`
+    pub channel: String,
+    /// The message's timestamp, e.g. `1714564800.000100`. See [`SlackTime::message_ts()`]
+    ///
+    /// [`SlackTime::message_ts()`]: crate::SlackTime::message_ts
+    pub ts: String,
+}
+
+impl MessageRef {
+    /// Construct a reference to a message
+    pub fn new<C: Into<String>, T: Into<String>>(channel: C, ts: T) -> MessageRef {
+        MessageRef {
+            channel: channel.into(),
+            ts: ts.into(),
+        }
+    }
+}
+
+/// The reactions and pins methods call the message's `ts` a `timestamp`
+#[derive(Serialize)]
+struct Timestamped<'a> {
+    channel: &'a str,
+    timestamp: &'a str,
+}
+
+impl<'a> From<&'a MessageRef> for Timestamped<'a> {
+    fn from(message: &'a MessageRef) -> Timestamped<'a> {
+        Timestamped {
+            channel: &message.channel,
+            timestamp: &message.ts,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Reaction<'a> {
+    #[serde(flatten)]
+    message: Timestamped<'a>,
+    name: &'a str,
+}
+
+#[derive(Deserialize)]
+struct Permalink {
+    permalink: Url,
+}
+
+#[derive(Deserialize)]
+struct History {
+    messages: Vec<HistoryMessage>,
+}
+
+#[derive(Deserialize)]
+struct HistoryMessage {
+    ts: String,
+    #[serde(default)]
+    metadata: Option<MessageMetadata>,
+}
+
+/// Where the list methods say the next page starts, which is empty on the last page
+#[derive(Deserialize, Default)]
+struct ResponseMetadata {
+    #[serde(default)]
+    next_cursor: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Error;
+    use insta::assert_snapshot;
+    use serde_json::{json, Value};
+    use wiremock::{
+        matchers::{body_json, body_string, header, method, path},
+        Mock, MockBuilder, MockServer, ResponseTemplate,
+    };
+
+    fn message() -> MessageRef {
+        MessageRef::new("C0123", "1714564800.000100")
+    }
+
+    fn mock(api_method: &str) -> MockBuilder {
+        Mock::given(method("POST"))
+            .and(path(format!("/{api_method}")))
+            .and(header("authorization", "Bearer xoxb-token"))
+    }
+
+    fn respond(body: Value) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(body)
+    }
+
+    fn web_api(server: &MockServer) -> WebApi {
+        WebApi::new("xoxb-token").api_url(server.uri().parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn reactions() {
+        let server = MockServer::start().await;
+        mock("reactions.add")
+            .and(body_json(json!({
+                "channel": "C0123",
+                "timestamp": "1714564800.000100",
+                "name": "white_check_mark"
+            })))
+            .respond_with(respond(json!({ "ok": true })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        web_api(&server)
+            .add_reaction(&message(), ":white_check_mark:")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn pins() {
+        let server = MockServer::start().await;
+        for api_method in ["pins.add", "pins.remove"] {
+            mock(api_method)
+                .and(body_json(json!({
+                    "channel": "C0123",
+                    "timestamp": "1714564800.000100"
+                })))
+                .respond_with(respond(json!({ "ok": true })))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let api = web_api(&server);
+        api.pin(&message()).await.unwrap();
+        api.unpin(&message()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn permalink() {
+        let server = MockServer::start().await;
+        let response = json!({
+            "ok": true,
+            "channel": "C0123",
+            "permalink": "https://example.slack.com/archives/C0123/p1714564800000100"
+        });
+        mock("chat.getPermalink")
+            .and(body_string("channel=C0123&message_ts=1714564800.000100"))
+            .respond_with(respond(response))
+            .mount(&server)
+            .await;
+
+        let permalink = web_api(&server).permalink(&message()).await.unwrap();
+        assert_snapshot!(permalink, @"https://example.slack.com/archives/C0123/p1714564800000100");
+    }
+
+    #[tokio::test]
+    async fn message_metadata() {
+        #[derive(Deserialize)]
+        struct Build {
+            version: String,
+        }
+
+        let server = MockServer::start().await;
+        let response = json!({
+            "ok": true,
+            "messages": [{
+                "type": "message",
+                "ts": "1714564800.000100",
+                "text": "Deployed 1.2.3",
+                "metadata": {
+                    "event_type": "deploy_finished",
+                    "event_payload": { "version": "1.2.3" }
+                }
+            }],
+            "has_more": false
+        });
+        mock("conversations.history")
+            .and(body_string(
+                "channel=C0123&latest=1714564800.000100&oldest=1714564800.000100\
+                 &inclusive=true&limit=1&include_all_metadata=true",
+            ))
+            .respond_with(respond(response))
+            .mount(&server)
+            .await;
+
+        let metadata = web_api(&server)
+            .message_metadata::<Build>(&message())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.event_type, "deploy_finished");
+        assert_eq!(metadata.event_payload.version, "1.2.3");
+    }
+
+    #[tokio::test]
+    async fn api_error() {
+        let server = MockServer::start().await;
+        mock("reactions.remove")
+            .respond_with(respond(json!({ "ok": false, "error": "no_reaction" })))
+            .mount(&server)
+            .await;
+
+        let err = web_api(&server)
+            .remove_reaction(&message(), "eyes")
+            .await
+            .unwrap_err();
+        assert_snapshot!(err, @"slack api error: no_reaction");
+    }
+
+    #[tokio::test]
+    async fn rate_limited() {
+        let server = MockServer::start().await;
+        mock("reactions.add")
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "30"))
+            .mount(&server)
+            .await;
+
+        let err = web_api(&server)
+            .add_reaction(&message(), "eyes")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::RateLimited(_)), "{:?}", err);
+        assert_snapshot!(err, @"slack rate limit hit, retry after 30s");
+    }
+
+    #[test]
+    fn redacts_token() {
+        let debug = format!("{:?}", WebApi::new("xoxb-secret"));
+        assert!(debug.contains("[redacted]"));
+        assert!(!debug.contains("xoxb-secret"));
+    }
+}
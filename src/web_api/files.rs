@@ -0,0 +1,276 @@
+use super::{MessageRef, WebApi};
+use crate::api;
+use crate::{Error, Result, SlackText};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// A file to upload with [`WebApi::upload_file()`], e.g. logs or a report too large for a message
+///
+/// ```rust,no_run
+/// # async fn run() -> slack_hook::Result<()> {
+/// use slack_hook::web_api::{FileUpload, WebApi};
+///
+/// let api = WebApi::new("xoxb-bot-token");
+/// let upload = FileUpload::new("errors.log", std::fs::read("errors.log")?)
+///     .channel("C0123456")
+///     .initial_comment("Logs from the failed deploy");
+/// let file = api.upload_file(upload).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileUpload {
+    filename: String,
+    content: Vec<u8>,
+    title: Option<String>,
+    channel_id: Option<String>,
+    initial_comment: Option<SlackText>,
+    thread_ts: Option<String>,
+}
+
+impl FileUpload {
+    /// Construct an upload of `content` named `filename`
+    ///
+    /// Without a channel the file is uploaded privately, to be shared later.
+    pub fn new<S: Into<String>, C: Into<Vec<u8>>>(filename: S, content: C) -> FileUpload {
+        FileUpload {
+            filename: filename.into(),
+            content: content.into(),
+            title: None,
+            channel_id: None,
+            initial_comment: None,
+            thread_ts: None,
+        }
+    }
+
+    /// Title shown instead of the filename
+    pub fn title<S: Into<String>>(mut self, title: S) -> FileUpload {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Share the file to a channel, by ID
+    pub fn channel<S: Into<String>>(mut self, channel_id: S) -> FileUpload {
+        self.channel_id = Some(channel_id.into());
+        self
+    }
+
+    /// Share the file as a reply in a message's thread
+    pub fn thread(mut self, message: &MessageRef) -> FileUpload {
+        self.channel_id = Some(message.channel.clone());
+        self.thread_ts = Some(message.ts.clone());
+        self
+    }
+
+    /// Message posted along with the file when it is shared
+    pub fn initial_comment<S: Into<SlackText>>(mut self, comment: S) -> FileUpload {
+        self.initial_comment = Some(comment.into());
+        self
+    }
+}
+
+/// A file uploaded by [`WebApi::upload_file()`]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct UploadedFile {
+    /// ID of the file, e.g. `F0123456`
+    pub id: String,
+    /// Link to the file in slack
+    pub permalink: Url,
+}
+
+impl WebApi {
+    /// Upload a file with slack's external upload flow
+    ///
+    /// This gets an upload url from `files.getUploadURLExternal`, sends the content to it, shares
+    /// the file with `files.completeUploadExternal` and then looks up its link with `files.info`.
+    pub async fn upload_file(&self, upload: FileUpload) -> Result<UploadedFile> {
+        let FileUpload {
+            filename,
+            content,
+            title,
+            channel_id,
+            initial_comment,
+            thread_ts,
+        } = upload;
+        let length = content.len().to_string();
+        let target: UploadTarget = api::post_form(
+            &self.client,
+            &self.api_url,
+            &self.token,
+            "files.getUploadURLExternal",
+            &[("filename", filename.as_str()), ("length", length.as_str())],
+        )
+        .await?;
+
+        let response = self
+            .client
+            .post(target.upload_url.clone())
+            .body(content)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(Error::Slack(format!("HTTP error {}", response.status())));
+        }
+
+        let complete = CompleteUpload {
+            files: [FileSummary {
+                id: &target.file_id,
+                title: title.as_deref(),
+            }],
+            channel_id: channel_id.as_deref(),
+            initial_comment: initial_comment.as_ref(),
+            thread_ts: thread_ts.as_deref(),
+        };
+        self.call_ok("files.completeUploadExternal", &complete)
+            .await?;
+
+        // completing the upload only returns the file's id and title
+        let info: FileInfo = api::post_form(
+            &self.client,
+            &self.api_url,
+            &self.token,
+            "files.info",
+            &[("file", target.file_id.as_str())],
+        )
+        .await?;
+        Ok(info.file)
+    }
+}
+
+#[derive(Deserialize)]
+struct UploadTarget {
+    upload_url: Url,
+    file_id: String,
+}
+
+#[derive(Serialize)]
+struct CompleteUpload<'a> {
+    files: [FileSummary<'a>; 1],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    initial_comment: Option<&'a SlackText>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_ts: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct FileSummary<'a> {
+    id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct FileInfo {
+    file: UploadedFile,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_json, body_string, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[tokio::test]
+    async fn uploads_to_a_thread() {
+        let server = MockServer::start().await;
+        let target = json!({
+            "ok": true,
+            "upload_url": format!("{}/upload/F123", server.uri()),
+            "file_id": "F123"
+        });
+        Mock::given(method("POST"))
+            .and(path("/files.getUploadURLExternal"))
+            .and(header("authorization", "Bearer xoxb-token"))
+            .and(body_string("filename=report.csv&length=11"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(target))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/upload/F123"))
+            .and(body_string("a,b\n1,2\n3,4"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let completed = json!({
+            "ok": true,
+            "files": [{ "id": "F123", "title": "Weekly report" }]
+        });
+        Mock::given(method("POST"))
+            .and(path("/files.completeUploadExternal"))
+            .and(header("authorization", "Bearer xoxb-token"))
+            .and(body_json(json!({
+                "files": [{ "id": "F123", "title": "Weekly report" }],
+                "channel_id": "C0123",
+                "initial_comment": "costs &lt;up&gt; 3%",
+                "thread_ts": "1714564800.000100"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(completed))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let info = json!({
+            "ok": true,
+            "file": {
+                "id": "F123",
+                "title": "Weekly report",
+                "name": "report.csv",
+                "permalink": "https://example.slack.com/files/U1/F123/report.csv"
+            }
+        });
+        Mock::given(method("POST"))
+            .and(path("/files.info"))
+            .and(header("authorization", "Bearer xoxb-token"))
+            .and(body_string("file=F123"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(info))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = WebApi::new("xoxb-token").api_url(server.uri().parse().unwrap());
+        let upload = FileUpload::new("report.csv", "a,b\n1,2\n3,4")
+            .title("Weekly report")
+            .thread(&MessageRef::new("C0123", "1714564800.000100"))
+            .initial_comment("costs <up> 3%");
+        let file = api.upload_file(upload).await.unwrap();
+        assert_eq!(file.id, "F123");
+        assert_eq!(
+            file.permalink.as_str(),
+            "https://example.slack.com/files/U1/F123/report.csv"
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_upload() {
+        let server = MockServer::start().await;
+        let target = json!({
+            "ok": true,
+            "upload_url": format!("{}/upload/F123", server.uri()),
+            "file_id": "F123"
+        });
+        Mock::given(method("POST"))
+            .and(path("/files.getUploadURLExternal"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(target))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/upload/F123"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let api = WebApi::new("xoxb-token").api_url(server.uri().parse().unwrap());
+        let err = api
+            .upload_file(FileUpload::new("empty.txt", ""))
+            .await
+            .unwrap_err();
+        assert_snapshot!(err, @"slack service error: HTTP error 500 Internal Server Error");
+    }
+}
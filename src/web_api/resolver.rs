@@ -0,0 +1,374 @@
+use super::{ResponseMetadata, WebApi};
+use crate::api;
+use crate::{Error, Result, SlackChannelLink, SlackUserLink};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How old the channel list must be before a name missing from it fetches it again
+const DEFAULT_MISS_TTL: Duration = Duration::from_secs(60);
+
+/// Page size requested from `conversations.list`, the most slack allows
+const PAGE_LIMIT: &str = "1000";
+
+/// Resolves channel names and user emails into the IDs that messages mention them by
+///
+/// Lookups are cached for an hour by default, see [`Resolver::ttl()`]. Channel names are resolved
+/// by listing every public and private channel the token can see, while each email is looked up
+/// on its own. A name missing from the list fetches it again once it is a minute old, so new
+/// channels are found, see [`Resolver::miss_ttl()`].
+///
+/// ```rust,no_run
+/// # async fn run() -> slack_hook::Result<()> {
+/// use slack_hook::web_api::{Resolver, WebApi};
+///
+/// let resolver = Resolver::new(WebApi::new("xoxb-bot-token"));
+/// let channel = resolver.channel_id("#alerts").await?;
+/// let on_call = resolver.user_link("oncall@example.com").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Resolver {
+    api: WebApi,
+    ttl: Duration,
+    miss_ttl: Duration,
+    channels: Mutex<Option<Cached<HashMap<String, String>>>>,
+    users: Mutex<HashMap<String, Cached<String>>>,
+}
+
+#[derive(Debug, Clone)]
+struct Cached<T> {
+    value: T,
+    fetched: Instant,
+}
+
+impl Resolver {
+    /// Construct a resolver that looks names up with `api`
+    pub fn new(api: WebApi) -> Resolver {
+        Resolver {
+            api,
+            ttl: DEFAULT_TTL,
+            miss_ttl: DEFAULT_MISS_TTL,
+            channels: Mutex::new(None),
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set how long lookups are cached for
+    pub fn ttl(mut self, ttl: Duration) -> Resolver {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set how old the channel list must be before a name missing from it fetches it again
+    ///
+    /// This bounds how often unknown names hit `conversations.list`, while still finding channels
+    /// created since the list was fetched. It is never longer than [`Resolver::ttl()`].
+    pub fn miss_ttl(mut self, miss_ttl: Duration) -> Resolver {
+        self.miss_ttl = miss_ttl;
+        self
+    }
+
+    /// Forget everything that has been cached
+    pub fn clear(&self) {
+        *lock(&self.channels) = None;
+        lock(&self.users).clear();
+    }
+
+    /// Look up the ID of a channel from its name, with or without the leading `#`
+    ///
+    /// Anything that already looks like a channel ID, e.g. `C0123456`, is returned as-is. Fails
+    /// with `Error::Api("channel_not_found")`, the same as the Web API does, when no channel the
+    /// token can see has the name.
+    pub async fn channel_id(&self, name: &str) -> Result<String> {
+        let name = name.trim_start_matches('#');
+        if is_channel_id(name) {
+            return Ok(name.to_owned());
+        }
+
+        let cached = lock(&self.channels).as_ref().and_then(|cached| {
+            let age = cached.fetched.elapsed();
+            match cached.value.get(name) {
+                Some(id) if age < self.ttl => Some(Some(id.clone())),
+                None if age < self.ttl.min(self.miss_ttl) => Some(None),
+                _ => None,
+            }
+        });
+        let id = match cached {
+            Some(id) => id,
+            None => {
+                let channels = self.list_channels().await?;
+                let id = channels.get(name).cloned();
+                *lock(&self.channels) = Some(Cached {
+                    value: channels,
+                    fetched: Instant::now(),
+                });
+                id
+            }
+        };
+        id.ok_or_else(|| Error::Api("channel_not_found".to_owned()))
+    }
+
+    /// Look up a channel by name and mention it
+    pub async fn channel_link(&self, name: &str) -> Result<SlackChannelLink> {
+        Ok(SlackChannelLink::new(&self.channel_id(name).await?))
+    }
+
+    /// Look up the ID of a user from their email address
+    pub async fn user_id(&self, email: &str) -> Result<String> {
+        let email = email.trim().to_lowercase();
+        if let Some(cached) = lock(&self.users)
+            .get(&email)
+            .filter(|cached| cached.fetched.elapsed() < self.ttl)
+        {
+            return Ok(cached.value.clone());
+        }
+
+        let response: UserLookup = api::post_form(
+            &self.api.client,
+            &self.api.api_url,
+            &self.api.token,
+            "users.lookupByEmail",
+            &[("email", email.as_str())],
+        )
+        .await?;
+        let _ = lock(&self.users).insert(
+            email,
+            Cached {
+                value: response.user.id.clone(),
+                fetched: Instant::now(),
+            },
+        );
+        Ok(response.user.id)
+    }
+
+    /// Look up a user by email and mention them
+    pub async fn user_link(&self, email: &str) -> Result<SlackUserLink> {
+        let id = self.user_id(email).await?;
+        Ok(SlackUserLink::new(&format!("@{id}")))
+    }
+
+    /// Page through every channel, keyed by name
+    async fn list_channels(&self) -> Result<HashMap<String, String>> {
+        let mut channels = HashMap::new();
+        let mut cursor = String::new();
+        loop {
+            let mut params = vec![
+                ("types", "public_channel,private_channel"),
+                ("exclude_archived", "true"),
+                ("limit", PAGE_LIMIT),
+            ];
+            if !cursor.is_empty() {
+                params.push(("cursor", cursor.as_str()));
+            }
+            let page: ChannelPage = api::post_form(
+                &self.api.client,
+                &self.api.api_url,
+                &self.api.token,
+                "conversations.list",
+                &params,
+            )
+            .await?;
+            channels.extend(
+                page.channels
+                    .into_iter()
+                    .map(|channel| (channel.name, channel.id)),
+            );
+
+            match page.response_metadata.next_cursor {
+                next if next.is_empty() => return Ok(channels),
+                next => cursor = next,
+            }
+        }
+    }
+}
+
+/// Channel IDs are upper case, unlike channel names
+fn is_channel_id(name: &str) -> bool {
+    name.starts_with(['C', 'G', 'D'])
+        && name.len() > 1
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+/// Lock a cache, ignoring poisoning since the caches are always left in a valid state
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[derive(Deserialize)]
+struct ChannelPage {
+    channels: Vec<Channel>,
+    #[serde(default)]
+    response_metadata: ResponseMetadata,
+}
+
+#[derive(Deserialize)]
+struct Channel {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct UserLookup {
+    user: User,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_string, body_string_contains, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn resolver(server: &MockServer) -> Resolver {
+        Resolver::new(WebApi::new("xoxb-token").api_url(server.uri().parse().unwrap()))
+    }
+
+    async fn mock_channels(server: &MockServer) {
+        let first = json!({
+            "ok": true,
+            "channels": [{ "id": "C1", "name": "general" }],
+            "response_metadata": { "next_cursor": "page2" }
+        });
+        let second = json!({
+            "ok": true,
+            "channels": [{ "id": "C2", "name": "alerts" }],
+            "response_metadata": { "next_cursor": "" }
+        });
+        Mock::given(method("POST"))
+            .and(path("/conversations.list"))
+            .and(body_string_contains("&cursor=page2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(second))
+            .expect(1)
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/conversations.list"))
+            .and(body_string(
+                "types=public_channel%2Cprivate_channel&exclude_archived=true&limit=1000",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(first))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn channels_are_paginated_and_cached() {
+        let server = MockServer::start().await;
+        mock_channels(&server).await;
+
+        let resolver = resolver(&server);
+        assert_eq!(resolver.channel_id("#alerts").await.unwrap(), "C2");
+        assert_eq!(resolver.channel_id("general").await.unwrap(), "C1");
+        assert_snapshot!(resolver.channel_link("#alerts").await.unwrap(), @"<#C2>");
+        match resolver.channel_id("#nope").await.unwrap_err() {
+            Error::Api(code) => assert_eq!(code, "channel_not_found"),
+            err => panic!("expected an api error, got {:?}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_channels_are_refetched() {
+        let server = MockServer::start().await;
+        let before = json!({ "ok": true, "channels": [{ "id": "C1", "name": "general" }] });
+        let after = json!({
+            "ok": true,
+            "channels": [{ "id": "C1", "name": "general" }, { "id": "C2", "name": "incident-42" }]
+        });
+        Mock::given(method("POST"))
+            .and(path("/conversations.list"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(before))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/conversations.list"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(after))
+            .mount(&server)
+            .await;
+
+        let resolver = resolver(&server).miss_ttl(Duration::ZERO);
+        assert!(resolver.channel_id("#incident-42").await.is_err());
+        assert_eq!(resolver.channel_id("#incident-42").await.unwrap(), "C2");
+        // names that are found keep using the cached list
+        assert_eq!(resolver.channel_id("general").await.unwrap(), "C1");
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn misses_within_miss_ttl_are_cached() {
+        let server = MockServer::start().await;
+        let page = json!({ "ok": true, "channels": [{ "id": "C1", "name": "general" }] });
+        Mock::given(method("POST"))
+            .and(path("/conversations.list"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let resolver = resolver(&server);
+        assert!(resolver.channel_id("#nope").await.is_err());
+        assert!(resolver.channel_id("#nope").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn expired_channels_are_refetched() {
+        let server = MockServer::start().await;
+        let page = json!({ "ok": true, "channels": [{ "id": "C1", "name": "general" }] });
+        Mock::given(method("POST"))
+            .and(path("/conversations.list"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let resolver = resolver(&server).ttl(Duration::ZERO);
+        assert_eq!(resolver.channel_id("general").await.unwrap(), "C1");
+        assert_eq!(resolver.channel_id("general").await.unwrap(), "C1");
+    }
+
+    #[tokio::test]
+    async fn channel_ids_pass_through() {
+        let server = MockServer::start().await;
+        let resolver = resolver(&server);
+        assert_eq!(resolver.channel_id("C0123ABC").await.unwrap(), "C0123ABC");
+    }
+
+    #[tokio::test]
+    async fn users_are_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/users.lookupByEmail"))
+            .and(body_string("email=oncall%40example.com"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "ok": true, "user": { "id": "U42" } })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let resolver = resolver(&server);
+        assert_snapshot!(resolver.user_link("OnCall@example.com").await.unwrap(), @"<@U42>");
+        assert_eq!(resolver.user_id("oncall@example.com").await.unwrap(), "U42");
+    }
+}
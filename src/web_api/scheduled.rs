@@ -0,0 +1,235 @@
+use super::{ResponseMetadata, WebApi};
+use crate::api;
+use crate::{Payload, Result, SlackTime};
+use serde::{Deserialize, Serialize};
+
+/// Page size requested from `chat.scheduledMessages.list`
+const PAGE_LIMIT: &str = "100";
+
+/// A message waiting to be posted, see [`WebApi::schedule_message()`]
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledMessage {
+    /// ID of the scheduled message, e.g. `Q0123456`
+    #[serde(alias = "scheduled_message_id")]
+    pub id: String,
+    /// ID of the channel it will be posted to
+    #[serde(alias = "channel_id")]
+    pub channel: String,
+    /// When it will be posted
+    pub post_at: SlackTime,
+}
+
+impl WebApi {
+    /// Have slack post `payload` to a channel at a later time
+    ///
+    /// The payload's own `channel` is replaced by `channel`. Slack only accepts times up to 120
+    /// days ahead, and sub-second precision is dropped.
+    ///
+    /// ```rust,no_run
+    /// # async fn run() -> slack_hook::Result<()> {
+    /// use slack_hook::{PayloadBuilder, SlackTime};
+    /// use slack_hook::web_api::WebApi;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let api = WebApi::new("xoxb-bot-token");
+    /// let payload = PayloadBuilder::new()
+    ///     .text("Maintenance starts in 10 minutes")
+    ///     .build()?;
+    /// let post_at = SlackTime::from(SystemTime::now() + Duration::from_secs(50 * 60));
+    /// let scheduled = api.schedule_message("C0123456", post_at, &payload).await?;
+    /// // plans changed
+    /// api.delete_scheduled_message(&scheduled.channel, &scheduled.id).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn schedule_message(
+        &self,
+        channel: &str,
+        post_at: SlackTime,
+        payload: &Payload,
+    ) -> Result<ScheduledMessage> {
+        let payload = Payload {
+            channel: Some(channel.to_owned()),
+            ..payload.clone()
+        };
+        let body = ScheduleMessage {
+            payload: &payload,
+            post_at,
+        };
+        self.call("chat.scheduleMessage", &body).await
+    }
+
+    /// List the messages waiting to be posted, either to one channel or to every channel
+    pub async fn scheduled_messages(&self, channel: Option<&str>) -> Result<Vec<ScheduledMessage>> {
+        let mut messages = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let mut params = vec![("limit", PAGE_LIMIT)];
+            if let Some(channel) = channel {
+                params.push(("channel", channel));
+            }
+            if !cursor.is_empty() {
+                params.push(("cursor", cursor.as_str()));
+            }
+            let page: ScheduledPage = api::post_form(
+                &self.client,
+                &self.api_url,
+                &self.token,
+                "chat.scheduledMessages.list",
+                &params,
+            )
+            .await?;
+            messages.extend(page.scheduled_messages);
+
+            match page.response_metadata.next_cursor {
+                next if next.is_empty() => return Ok(messages),
+                next => cursor = next,
+            }
+        }
+    }
+
+    /// Stop a scheduled message from being posted
+    pub async fn delete_scheduled_message(&self, channel: &str, id: &str) -> Result<()> {
+        let body = DeleteScheduledMessage {
+            channel,
+            scheduled_message_id: id,
+        };
+        self.call_ok("chat.deleteScheduledMessage", &body).await
+    }
+}
+
+#[derive(Serialize)]
+struct ScheduleMessage<'a> {
+    #[serde(flatten)]
+    payload: &'a Payload,
+    post_at: SlackTime,
+}
+
+#[derive(Serialize)]
+struct DeleteScheduledMessage<'a> {
+    channel: &'a str,
+    scheduled_message_id: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ScheduledPage {
+    scheduled_messages: Vec<ScheduledMessage>,
+    #[serde(default)]
+    response_metadata: ResponseMetadata,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PayloadBuilder;
+    use insta::assert_debug_snapshot;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_json, body_string, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn web_api(server: &MockServer) -> WebApi {
+        WebApi::new("xoxb-token").api_url(server.uri().parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn schedule_and_delete() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat.scheduleMessage"))
+            .and(body_json(json!({
+                "text": "Maintenance starts in 10 minutes",
+                "channel": "C0123",
+                "post_at": 1_714_564_800
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "ok": true,
+                "channel": "C0123",
+                "scheduled_message_id": "Q1298393284",
+                "post_at": "1714564800",
+                "message": { "text": "Maintenance starts in 10 minutes" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat.deleteScheduledMessage"))
+            .and(body_json(json!({
+                "channel": "C0123",
+                "scheduled_message_id": "Q1298393284"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let payload = PayloadBuilder::new()
+            .text("Maintenance starts in 10 minutes")
+            .channel("#ignored")
+            .build()
+            .unwrap();
+        let api = web_api(&server);
+        let scheduled = api
+            .schedule_message("C0123", SlackTime::from_unix(1_714_564_800, 5), &payload)
+            .await
+            .unwrap();
+        assert_debug_snapshot!(scheduled, @r###"
+        ScheduledMessage {
+            id: "Q1298393284",
+            channel: "C0123",
+            post_at: SlackTime {
+                secs: 1714564800,
+                nanos: 0,
+            },
+        }
+        "###);
+        api.delete_scheduled_message(&scheduled.channel, &scheduled.id)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn lists_every_page() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat.scheduledMessages.list"))
+            .and(body_string("limit=100&channel=C0123"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "ok": true,
+                "scheduled_messages": [{
+                    "id": "Q1",
+                    "channel_id": "C0123",
+                    "post_at": 1_714_564_800,
+                    "date_created": 1_714_500_000,
+                    "text": "first"
+                }],
+                "response_metadata": { "next_cursor": "page2" }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat.scheduledMessages.list"))
+            .and(body_string("limit=100&channel=C0123&cursor=page2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "ok": true,
+                "scheduled_messages": [{
+                    "id": "Q2",
+                    "channel_id": "C0123",
+                    "post_at": 1_714_568_400,
+                    "date_created": 1_714_500_000,
+                    "text": "second"
+                }],
+                "response_metadata": { "next_cursor": "" }
+            })))
+            .mount(&server)
+            .await;
+
+        let messages = web_api(&server)
+            .scheduled_messages(Some("C0123"))
+            .await
+            .unwrap();
+        let ids: Vec<_> = messages.iter().map(|message| message.id.as_str()).collect();
+        assert_eq!(ids, ["Q1", "Q2"]);
+    }
+}
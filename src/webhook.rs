@@ -0,0 +1,437 @@
+use crate::error::{Error, Result};
+use crate::Dialect;
+use serde::Deserialize;
+use std::{convert::TryFrom, fmt, str::FromStr};
+use url::{Position, Url};
+
+/// Hosts that serve slack's incoming webhooks
+pub(crate) const WEBHOOK_HOSTS: [&str; 2] = ["hooks.slack.com", "hooks.slack-gov.com"];
+
+/// Hosts that serve Discord's webhooks
+const DISCORD_HOSTS: [&str; 4] = [
+    "discord.com",
+    "discordapp.com",
+    "ptb.discord.com",
+    "canary.discord.com",
+];
+
+const REDACTED: &str = "[redacted]";
+
+/// An incoming webhook url, e.g. `https://hooks.slack.com/services/T000/B000/XXXX`
+///
+/// The path holds a secret that lets anyone post to the channel, so it is redacted in both the
+/// `Debug` and `Display` output. Use [`WebhookUrl::as_url()`] to get at the full url.
+///
+/// Urls for other chat systems that take slack's payloads are parsed with
+/// [`WebhookUrl::parse_dialect()`]. Deserializing takes either a slack url on its own or a map
+/// holding the `url` and its `dialect`, e.g. `{ url = "http://chat.internal/hooks/<key>", dialect
+/// = "mattermost" }`.
+#[derive(Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "WebhookUrlDef")]
+pub struct WebhookUrl {
+    url: Url,
+    dialect: Dialect,
+}
+
+impl WebhookUrl {
+    /// Parse and validate a webhook url
+    pub fn parse(s: &str) -> Result<WebhookUrl> {
+        WebhookUrl::try_from(Url::parse(s)?)
+    }
+
+    /// Parse and validate a webhook url for a chat system that takes slack's payloads
+    ///
+    /// Discord urls have the `/slack` suffix added if it's missing. Mattermost and Rocket.Chat urls
+    /// may use plain http, since those servers are often self-hosted on an internal network.
+    pub fn parse_dialect(s: &str, dialect: Dialect) -> Result<WebhookUrl> {
+        let mut url = Url::parse(s)?;
+        let self_hosted = matches!(dialect, Dialect::Mattermost | Dialect::RocketChat);
+        if self_hosted && url.scheme() == "http" {
+            // allowed as is
+        } else if url.scheme() != "https" {
+            return Err(Error::WebhookUrl(format!(
+                "Must use https: found `{}`",
+                url.scheme()
+            )));
+        }
+
+        let segments: Vec<_> = url.path_segments().into_iter().flatten().collect();
+        let valid = match (dialect, segments.as_slice()) {
+            (Dialect::Slack, _) => return WebhookUrl::try_from(url),
+            (Dialect::Mattermost, [.., "hooks", key]) => !key.is_empty(),
+            (Dialect::RocketChat, [.., "hooks", id, token]) => !id.is_empty() && !token.is_empty(),
+            (Dialect::Discord, ["api", rest @ ..]) => {
+                let host = url.host_str().unwrap_or_default();
+                if !DISCORD_HOSTS.contains(&host) {
+                    return Err(Error::WebhookUrl(format!(
+                        "Must be one of {DISCORD_HOSTS:?}: found host `{host}`"
+                    )));
+                }
+                let rest = match rest {
+                    // an api version, e.g. `v10`
+                    [version, rest @ ..] if version.starts_with('v') => rest,
+                    rest => rest,
+                };
+                match rest {
+                    ["webhooks", id, token, "slack"] | ["webhooks", id, token] => {
+                        !id.is_empty() && !token.is_empty()
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        };
+        if !valid {
+            return Err(Error::WebhookUrl(format!(
+                "Path must look like `{}`",
+                match dialect {
+                    Dialect::Slack => unreachable!("slack urls are validated above"),
+                    Dialect::Mattermost => "/hooks/<key>",
+                    Dialect::RocketChat => "/hooks/<id>/<token>",
+                    Dialect::Discord => "/api/webhooks/<id>/<token>/slack",
+                }
+            )));
+        }
+
+        if dialect == Dialect::Discord && segments.last() != Some(&"slack") {
+            let _ = url
+                .path_segments_mut()
+                .expect("the url has a path")
+                .push("slack");
+        }
+        Ok(WebhookUrl { url, dialect })
+    }
+
+    /// Read and validate a webhook url from the environment variable `var`
+    pub fn from_env(var: &str) -> Result<WebhookUrl> {
+        match std::env::var(var) {
+            Ok(url) => url.trim().parse(),
+            Err(err) => Err(Error::Config(format!("`{var}`: {err}"))),
+        }
+    }
+
+    /// Skip validation so that tests can point at a local server
+    #[cfg(all(test, any(feature = "client", feature = "ureq")))]
+    pub(crate) fn new_unchecked(url: Url) -> WebhookUrl {
+        WebhookUrl {
+            url,
+            dialect: Dialect::Slack,
+        }
+    }
+
+    /// The team (workspace) id, e.g. `T000`, which is empty for other dialects
+    pub fn team(&self) -> &str {
+        self.slack_segment(1)
+    }
+
+    /// The id of the bot (or app) the webhook belongs to, e.g. `B000`, which is empty for other
+    /// dialects
+    pub fn bot(&self) -> &str {
+        self.slack_segment(2)
+    }
+
+    /// The full url, including the secret
+    pub fn as_url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The chat system the url is for
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    fn slack_segment(&self, index: usize) -> &str {
+        if self.dialect != Dialect::Slack {
+            return "";
+        }
+        self.url
+            .path_segments()
+            .and_then(|mut segments| segments.nth(index))
+            .expect("path was validated on construction")
+    }
+}
+
+impl TryFrom<Url> for WebhookUrl {
+    type Error = Error;
+
+    fn try_from(url: Url) -> Result<Self> {
+        if url.scheme() != "https" {
+            return Err(Error::WebhookUrl(format!(
+                "Must use https: found `{}`",
+                url.scheme()
+            )));
+        }
+
+        let host = url.host_str().unwrap_or_default();
+        if !WEBHOOK_HOSTS.contains(&host) {
+            return Err(Error::WebhookUrl(format!(
+                "Must be one of {WEBHOOK_HOSTS:?}: found host `{host}`"
+            )));
+        }
+
+        let segments: Vec<_> = url.path_segments().into_iter().flatten().collect();
+        match segments[..] {
+            ["services", team, bot, secret]
+                if !team.is_empty() && !bot.is_empty() && !secret.is_empty() =>
+            {
+                Ok(WebhookUrl {
+                    url,
+                    dialect: Dialect::Slack,
+                })
+            }
+            _ => Err(Error::WebhookUrl(
+                "Path must look like `/services/<team>/<bot>/<secret>`".to_owned(),
+            )),
+        }
+    }
+}
+
+/// The forms a [`WebhookUrl`] is deserialized from
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WebhookUrlDef {
+    Slack(String),
+    Dialect {
+        url: String,
+        #[serde(default)]
+        dialect: Dialect,
+    },
+}
+
+impl TryFrom<WebhookUrlDef> for WebhookUrl {
+    type Error = Error;
+
+    fn try_from(def: WebhookUrlDef) -> Result<Self> {
+        match def {
+            WebhookUrlDef::Slack(url) => WebhookUrl::parse(&url),
+            WebhookUrlDef::Dialect { url, dialect } => WebhookUrl::parse_dialect(&url, dialect),
+        }
+    }
+}
+
+impl FromStr for WebhookUrl {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        WebhookUrl::parse(s)
+    }
+}
+
+impl TryFrom<&str> for WebhookUrl {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl TryFrom<String> for WebhookUrl {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for WebhookUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = &self.url[..Position::AfterPath];
+        // Discord's secret token comes before its `/slack` suffix
+        let (path, suffix) = match self.dialect {
+            Dialect::Discord => path
+                .rsplit_once('/')
+                .map_or((path, ""), |(path, _)| (path, "/slack")),
+            _ => (path, ""),
+        };
+        let without_secret = path.rsplit_once('/').map_or("", |(prefix, _secret)| prefix);
+        write!(f, "{without_secret}/{REDACTED}{suffix}")
+    }
+}
+
+impl fmt::Debug for WebhookUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("WebhookUrl")
+            .field(&self.to_string())
+            .finish()
+    }
+}
+
+/// A trait to try to convert some type into a [`WebhookUrl`]
+///
+/// This is implemented for `WebhookUrl` itself along with string types and [`Url`].
+pub trait IntoWebhookUrl {
+    /// Try to convert `self` into a `WebhookUrl`
+    fn into_webhook_url(self) -> Result<WebhookUrl>;
+}
+
+impl IntoWebhookUrl for WebhookUrl {
+    fn into_webhook_url(self) -> Result<WebhookUrl> {
+        Ok(self)
+    }
+}
+
+impl IntoWebhookUrl for Url {
+    fn into_webhook_url(self) -> Result<WebhookUrl> {
+        WebhookUrl::try_from(self)
+    }
+}
+
+impl IntoWebhookUrl for &str {
+    fn into_webhook_url(self) -> Result<WebhookUrl> {
+        self.parse()
+    }
+}
+
+impl IntoWebhookUrl for &String {
+    fn into_webhook_url(self) -> Result<WebhookUrl> {
+        self.parse()
+    }
+}
+
+impl IntoWebhookUrl for String {
+    fn into_webhook_url(self) -> Result<WebhookUrl> {
+        self.parse()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    const HOOK: &str = "https://hooks.slack.com/services/T0001/B0002/s3cr3t";
+
+    #[test]
+    fn components() {
+        let hook = WebhookUrl::parse(HOOK).unwrap();
+        assert_eq!(hook.team(), "T0001");
+        assert_eq!(hook.bot(), "B0002");
+        assert_eq!(hook.as_url().as_str(), HOOK);
+    }
+
+    #[test]
+    fn gov_host() {
+        let hook = WebhookUrl::parse("https://hooks.slack-gov.com/services/T1/B2/s3cr3t").unwrap();
+        assert_eq!(hook.team(), "T1");
+    }
+
+    #[test]
+    fn redacted() {
+        let hook = WebhookUrl::parse(HOOK).unwrap();
+        assert_snapshot!(hook, @"https://hooks.slack.com/services/T0001/B0002/[redacted]");
+        assert_snapshot!(
+            format!("{hook:?}"),
+            @r###"WebhookUrl("https://hooks.slack.com/services/T0001/B0002/[redacted]")"###
+        );
+    }
+
+    #[test]
+    fn dialects() {
+        let mattermost = WebhookUrl::parse_dialect(
+            "https://chat.example.com/mattermost/hooks/xa8pw3ukqfbwjbhfp5jsnwjx1a",
+            Dialect::Mattermost,
+        )
+        .unwrap();
+        assert_eq!(mattermost.dialect(), Dialect::Mattermost);
+        assert_eq!(mattermost.team(), "");
+        assert_snapshot!(mattermost, @"https://chat.example.com/mattermost/hooks/[redacted]");
+
+        let rocket_chat = WebhookUrl::parse_dialect(
+            "https://chat.example.com/hooks/6f8k2/s3cr3t",
+            Dialect::RocketChat,
+        )
+        .unwrap();
+        assert_snapshot!(rocket_chat, @"https://chat.example.com/hooks/6f8k2/[redacted]");
+
+        let discord = WebhookUrl::parse_dialect(
+            "https://discord.com/api/webhooks/1234/s3cr3t",
+            Dialect::Discord,
+        )
+        .unwrap();
+        assert_snapshot!(discord.as_url(), @"https://discord.com/api/webhooks/1234/s3cr3t/slack");
+        assert_snapshot!(discord, @"https://discord.com/api/webhooks/1234/[redacted]/slack");
+
+        let slack = WebhookUrl::parse_dialect(HOOK, Dialect::Slack).unwrap();
+        assert_eq!(slack, WebhookUrl::parse(HOOK).unwrap());
+    }
+
+    #[test]
+    fn self_hosted_over_http() {
+        let mattermost =
+            WebhookUrl::parse_dialect("http://chat.internal/hooks/key", Dialect::Mattermost)
+                .unwrap();
+        assert_snapshot!(mattermost, @"http://chat.internal/hooks/[redacted]");
+        let rocket_chat =
+            WebhookUrl::parse_dialect("http://chat.internal/hooks/id/token", Dialect::RocketChat)
+                .unwrap();
+        assert_eq!(rocket_chat.dialect(), Dialect::RocketChat);
+
+        let err = WebhookUrl::parse_dialect("ftp://chat.internal/hooks/key", Dialect::Mattermost)
+            .unwrap_err();
+        assert_snapshot!(err, @"invalid webhook url: Must use https: found `ftp`");
+        let err = WebhookUrl::parse_dialect(
+            "http://discord.com/api/webhooks/1234/s3cr3t",
+            Dialect::Discord,
+        )
+        .unwrap_err();
+        assert_snapshot!(err, @"invalid webhook url: Must use https: found `http`");
+    }
+
+    #[test]
+    fn deserialize() {
+        let slack: WebhookUrl = serde_json::from_value(serde_json::json!(HOOK)).unwrap();
+        assert_eq!(slack, WebhookUrl::parse(HOOK).unwrap());
+
+        let mattermost: WebhookUrl = serde_json::from_value(serde_json::json!({
+            "url": "http://chat.internal/hooks/key",
+            "dialect": "mattermost"
+        }))
+        .unwrap();
+        assert_eq!(mattermost.dialect(), Dialect::Mattermost);
+
+        let err = serde_json::from_value::<WebhookUrl>(serde_json::json!({
+            "url": "http://chat.internal/hooks/key"
+        }))
+        .unwrap_err();
+        assert_snapshot!(err, @"invalid webhook url: Must use https: found `http`");
+    }
+
+    mod err {
+        use super::*;
+
+        #[test]
+        fn dialect_path() {
+            let err = WebhookUrl::parse_dialect(
+                "https://discord.com/api/channels/1234",
+                Dialect::Discord,
+            )
+            .unwrap_err();
+            assert_snapshot!(err, @"invalid webhook url: Path must look like `/api/webhooks/<id>/<token>/slack`");
+        }
+
+        #[test]
+        fn wrong_host() {
+            let err = WebhookUrl::parse("https://example.com/services/T1/B2/s3cr3t").unwrap_err();
+            assert_snapshot!(
+                err,
+                @r###"invalid webhook url: Must be one of ["hooks.slack.com", "hooks.slack-gov.com"]: found host `example.com`"###
+            );
+        }
+
+        #[test]
+        fn not_https() {
+            let err =
+                WebhookUrl::parse("http://hooks.slack.com/services/T1/B2/s3cr3t").unwrap_err();
+            assert_snapshot!(err, @"invalid webhook url: Must use https: found `http`");
+        }
+
+        #[test]
+        fn missing_secret() {
+            let err = WebhookUrl::parse("https://hooks.slack.com/services/T1/B2/").unwrap_err();
+            assert_snapshot!(
+                err,
+                @"invalid webhook url: Path must look like `/services/<team>/<bot>/<secret>`"
+            );
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use crate::webhook::WEBHOOK_HOSTS;
+use crate::{Error, Result};
+use reqwest::{Client, Response, Url};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Starts a Workflow Builder workflow through its webhook trigger
+///
+/// Workflow webhooks take a flat object of the variables declared in Workflow Builder instead of
+/// a [`Payload`](crate::Payload). Any `Serialize` struct or map can be sent, as long as each
+/// value is a string. `None` values are left out, so optional variables can be `Option` fields.
+///
+/// ```rust,no_run
+/// # async fn run() -> slack_hook::Result<()> {
+/// use slack_hook::WorkflowTrigger;
+///
+/// #[derive(serde::Serialize)]
+/// struct Page {
+///     service: String,
+///     summary: String,
+///     runbook: Option<String>,
+/// }
+///
+/// let trigger =
+///     WorkflowTrigger::new("https://hooks.slack.com/triggers/T0001/12345/s3cr3t")?;
+/// trigger
+///     .send(&Page {
+///         service: "api".to_owned(),
+///         summary: "p99 latency above 2s".to_owned(),
+///         runbook: None,
+///     })
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct WorkflowTrigger {
+    url: Url,
+    client: Client,
+}
+
+impl WorkflowTrigger {
+    /// Construct a trigger for a workflow webhook url, e.g.
+    /// `https://hooks.slack.com/triggers/T0001/12345/s3cr3t`
+    pub fn new(url: &str) -> Result<WorkflowTrigger> {
+        Self::new_with_client(url, Client::new())
+    }
+
+    /// The same as [`WorkflowTrigger::new()`], but with a custom [`reqwest::Client`]
+    pub fn new_with_client(url: &str, client: Client) -> Result<WorkflowTrigger> {
+        let url = Url::parse(url)?;
+        if url.scheme() != "https" {
+            return Err(Error::WebhookUrl(format!(
+                "Must use https: found `{}`",
+                url.scheme()
+            )));
+        }
+
+        let host = url.host_str().unwrap_or_default();
+        if !WEBHOOK_HOSTS.contains(&host) {
+            return Err(Error::WebhookUrl(format!(
+                "Must be one of {WEBHOOK_HOSTS:?}: found host `{host}`"
+            )));
+        }
+
+        let segments: Vec<_> = url.path_segments().into_iter().flatten().collect();
+        match segments.as_slice() {
+            ["triggers" | "workflows", rest @ ..]
+                if rest.len() >= 3 && rest.iter().all(|segment| !segment.is_empty()) =>
+            {
+                Ok(WorkflowTrigger { url, client })
+            }
+            _ => Err(Error::WebhookUrl(
+                "Path must look like `/triggers/<team>/<id>/<secret>`".to_owned(),
+            )),
+        }
+    }
+
+    /// Start the workflow with `variables`
+    pub async fn send<T: Serialize + ?Sized>(&self, variables: &T) -> Result<()> {
+        let variables = string_variables(serde_json::to_value(variables)?)?;
+        let response = self
+            .client
+            .post(self.url.clone())
+            .json(&variables)
+            .send()
+            .await
+            .map_err(without_url)?;
+        check_response(response).await
+    }
+}
+
+impl fmt::Debug for WorkflowTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let without_secret = self
+            .url
+            .as_str()
+            .rsplit_once('/')
+            .map_or("", |(prefix, _secret)| prefix);
+        f.debug_struct("WorkflowTrigger")
+            .field("url", &format!("{without_secret}/[redacted]"))
+            .finish()
+    }
+}
+
+/// Check that the variables are a flat object of strings, dropping any nulls
+fn string_variables(variables: Value) -> Result<Map<String, Value>> {
+    let variables = match variables {
+        Value::Object(variables) => variables,
+        other => {
+            return Err(Error::Workflow(format!(
+                "expected an object of variables, found {}",
+                kind(&other)
+            )))
+        }
+    };
+
+    let mut strings = Map::new();
+    for (name, value) in variables {
+        match value {
+            Value::Null => {}
+            Value::String(_) => {
+                let _ = strings.insert(name, value);
+            }
+            other => {
+                return Err(Error::Workflow(format!(
+                    "variable `{name}` must be a string, found {}",
+                    kind(&other)
+                )))
+            }
+        }
+    }
+    Ok(strings)
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Drop the url from a transport error, since its last segment is the trigger's secret
+fn without_url(err: reqwest::Error) -> Error {
+    Error::from(err.without_url())
+}
+
+/// Workflow webhooks name what went wrong in the body, either as JSON with an `error` or as
+/// plain text like `invalid_workflow_input`
+async fn check_response(response: Response) -> Result<()> {
+    let status = response.status();
+    let body = response.text().await.map_err(without_url)?;
+    let error = match serde_json::from_str::<Value>(&body) {
+        Ok(value) if value.get("ok").and_then(Value::as_bool) != Some(false) => None,
+        Ok(value) => Some(
+            value
+                .get("error")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown_error")
+                .to_owned(),
+        ),
+        Err(_) if !status.is_success() && !body.trim().is_empty() => Some(body.trim().to_owned()),
+        Err(_) => None,
+    };
+
+    match error {
+        Some(error) => Err(Error::Api(error)),
+        None if status.is_success() => Ok(()),
+        None => Err(Error::Slack(format!("HTTP error {status}"))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::refused_url;
+    use insta::assert_snapshot;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+    use wiremock::{
+        matchers::{body_json, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    const TRIGGER: &str = "https://hooks.slack.com/triggers/T0001/12345/s3cr3t";
+
+    fn trigger(server: &MockServer) -> WorkflowTrigger {
+        WorkflowTrigger {
+            url: format!("{}/triggers/T0001/12345/s3cr3t", server.uri())
+                .parse()
+                .unwrap(),
+            client: Client::new(),
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Page {
+        service: &'static str,
+        summary: &'static str,
+        runbook: Option<&'static str>,
+    }
+
+    #[tokio::test]
+    async fn sends_variables() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/triggers/T0001/12345/s3cr3t"))
+            .and(body_json(json!({
+                "service": "api",
+                "summary": "p99 latency above 2s"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let page = Page {
+            service: "api",
+            summary: "p99 latency above 2s",
+            runbook: None,
+        };
+        trigger(&server).send(&page).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn error_responses() {
+        let server = MockServer::start().await;
+        let mut variables = BTreeMap::new();
+        let _ = variables.insert("service", "api");
+
+        let response = ResponseTemplate::new(400).set_body_string("invalid_workflow_input");
+        let mock = Mock::given(method("POST"))
+            .respond_with(response)
+            .mount_as_scoped(&server)
+            .await;
+        let err = trigger(&server).send(&variables).await.unwrap_err();
+        assert_snapshot!(err, @"slack api error: invalid_workflow_input");
+        drop(mock);
+
+        let response = ResponseTemplate::new(200)
+            .set_body_json(json!({ "ok": false, "error": "trigger_not_found" }));
+        let mock = Mock::given(method("POST"))
+            .respond_with(response)
+            .mount_as_scoped(&server)
+            .await;
+        let err = trigger(&server).send(&variables).await.unwrap_err();
+        assert_snapshot!(err, @"slack api error: trigger_not_found");
+        drop(mock);
+
+        let _mock = Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount_as_scoped(&server)
+            .await;
+        let err = trigger(&server).send(&variables).await.unwrap_err();
+        assert_snapshot!(err, @"slack service error: HTTP error 503 Service Unavailable");
+    }
+
+    #[tokio::test]
+    async fn rejects_non_strings() {
+        let server = MockServer::start().await;
+        let trigger = trigger(&server);
+
+        let err = trigger
+            .send(&json!({ "service": "api", "count": 3 }))
+            .await
+            .unwrap_err();
+        assert_snapshot!(err, @"invalid workflow variables: variable `count` must be a string, found a number");
+        let err = trigger.send(&["api"]).await.unwrap_err();
+        assert_snapshot!(err, @"invalid workflow variables: expected an object of variables, found an array");
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn transport_error_hides_secret() {
+        let trigger = WorkflowTrigger {
+            url: refused_url("/triggers/T0001/12345/s3cr3t"),
+            client: Client::new(),
+        };
+        let err = trigger
+            .send(&json!({ "service": "api" }))
+            .await
+            .unwrap_err();
+        let err = format!("{err} {err:?}");
+        assert!(!err.contains("s3cr3t"), "{}", err);
+    }
+
+    #[test]
+    fn validates_url() {
+        let legacy = "https://hooks.slack.com/workflows/T0001/A0002/12345/s3cr3t";
+        assert!(WorkflowTrigger::new(legacy).is_ok());
+
+        let err = |url: &str| WorkflowTrigger::new(url).unwrap_err().to_string();
+        assert_snapshot!(err("https://hooks.slack.com/services/T1/B2/s3cr3t"), @"invalid webhook url: Path must look like `/triggers/<team>/<id>/<secret>`");
+        assert_snapshot!(
+            err("https://example.com/triggers/T1/1/s3cr3t"),
+            @r###"invalid webhook url: Must be one of ["hooks.slack.com", "hooks.slack-gov.com"]: found host `example.com`"###
+        );
+    }
+
+    #[test]
+    fn redacts_secret() {
+        let trigger = WorkflowTrigger::new(TRIGGER).unwrap();
+        assert_snapshot!(format!("{trigger:?}"), @r###"WorkflowTrigger { url: "https://hooks.slack.com/triggers/T0001/12345/[redacted]" }"###);
+    }
+}